@@ -0,0 +1,71 @@
+// Compares the cost of the submission policies, from submitting every batch
+// of draws on its own to recording the whole frame into one encoder. Run with
+// cargo bench --features testing. The submissions and render passes of a
+// frame are printed under the timings of each policy.
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::{checkerboard_material, quad, textured_pipeline};
+use criterion::{criterion_group, criterion_main, Criterion};
+use renderer2::{
+    testing::headless_renderer, AdaptiveTarget, FixedCount, FrameStatistics, OncePerFrame,
+    SubmissionPolicy,
+};
+use std::time::Instant;
+
+const MESHES: usize = 100;
+
+fn submission_policies(criterion: &mut Criterion) {
+    let policies: [(&str, Box<dyn SubmissionPolicy>); 3] = [
+        ("fixed_count", Box::new(FixedCount::default())),
+        ("adaptive_target", Box::new(AdaptiveTarget::new(4))),
+        ("once_per_frame", Box::new(OncePerFrame)),
+    ];
+    let mut group = criterion.benchmark_group("submission");
+    for (name, policy) in policies {
+        let mut renderer = headless_renderer(256, 256).unwrap();
+        renderer.set_submission_policy(policy);
+        let pipeline = renderer.register_pipeline(&textured_pipeline()).unwrap();
+        let material = renderer
+            .register_material(&checkerboard_material())
+            .unwrap();
+        let meshes: Vec<_> = (0..MESHES)
+            .map(|_| {
+                renderer
+                    .register_mesh(&quad(pipeline, material, [1.0, 1.0, 1.0, 1.0]))
+                    .unwrap()
+            })
+            .collect();
+
+        let mut statistics = FrameStatistics::default();
+        group.bench_function(name, |bencher| {
+            bencher.iter_custom(|frames| {
+                let start = Instant::now();
+                for _ in 0..frames {
+                    let mut frame = renderer.begin_frame();
+                    for mesh in &meshes {
+                        renderer.submit_mesh(&mut frame, *mesh).unwrap();
+                    }
+                    renderer.end_frame(frame).unwrap();
+                    statistics = renderer.last_frame_statistics();
+                }
+
+                // Reading back a frame waits for the GPU so the timing covers
+                // the work
+                let mut frame = renderer.begin_frame();
+                renderer.capture_frame(&mut frame).unwrap();
+                renderer.end_frame(frame).unwrap();
+                start.elapsed()
+            })
+        });
+        println!(
+            "submission/{name}: {} submissions, {} render passes, {} draws per frame",
+            statistics.submissions, statistics.render_passes, statistics.draws
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, submission_policies);
+criterion_main!(benches);
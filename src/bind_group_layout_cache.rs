@@ -0,0 +1,65 @@
+use std::{collections::HashMap, sync::Arc};
+use wgpu::{BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, Device, ErrorFilter};
+
+// Bind group layouts shared by pipelines, uniform groups and materials. Bind
+// groups are created against the same layout object as the pipelines they are
+// drawn with whenever their entries are identical, rather than each creating
+// a layout of its own.
+pub struct BindGroupLayoutCache {
+    layouts: HashMap<Vec<BindGroupLayoutEntry>, Arc<BindGroupLayout>>,
+}
+
+impl BindGroupLayoutCache {
+    pub fn new() -> Self {
+        Self {
+            layouts: HashMap::new(),
+        }
+    }
+
+    pub fn get_or_create(
+        &mut self,
+        device: &Device,
+        entries: &[BindGroupLayoutEntry],
+    ) -> Arc<BindGroupLayout> {
+        if let Some(layout) = self.layouts.get(entries) {
+            return layout.clone();
+        }
+
+        let layout = self.create(device, entries);
+        self.layouts.insert(entries.to_vec(), layout.clone());
+        layout
+    }
+
+    // Creates a missing layout inside a validation error scope, so that
+    // layouts the device rejects are returned as errors and never cached for
+    // the pipelines and groups created after them
+    pub fn try_get_or_create(
+        &mut self,
+        device: &Device,
+        entries: &[BindGroupLayoutEntry],
+    ) -> Result<Arc<BindGroupLayout>, wgpu::Error> {
+        if let Some(layout) = self.layouts.get(entries) {
+            return Ok(layout.clone());
+        }
+
+        device.push_error_scope(ErrorFilter::Validation);
+        let layout = self.create(device, entries);
+        if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+            return Err(error);
+        }
+        self.layouts.insert(entries.to_vec(), layout.clone());
+        Ok(layout)
+    }
+
+    fn create(&self, device: &Device, entries: &[BindGroupLayoutEntry]) -> Arc<BindGroupLayout> {
+        let label = format!("bind_group_layout({})", self.layouts.len());
+        Arc::new(device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some(&label),
+            entries,
+        }))
+    }
+
+    pub fn len(&self) -> usize {
+        self.layouts.len()
+    }
+}
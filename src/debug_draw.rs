@@ -0,0 +1,703 @@
+use crate::{
+    debug_font::{self, CELL_HEIGHT, CELL_WIDTH, GLYPH_HEIGHT, GLYPH_WIDTH},
+    error::RendererError,
+    frame::DrawTarget,
+    texture::Texture,
+    Renderer,
+};
+use glam::{Mat4, Quat, Vec2, Vec3};
+use std::{
+    f32::consts::TAU,
+    num::NonZeroU64,
+    time::{Duration, Instant},
+};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBinding, BufferBindingType,
+    BufferDescriptor, BufferUsages, CommandEncoder, CompareFunction, Device,
+    PipelineCompilationOptions, PipelineLayoutDescriptor, PrimitiveTopology, Queue, RenderPipeline,
+    RenderPipelineDescriptor, ShaderStages, TextureFormat, TextureSampleType, TextureViewDimension,
+    VertexAttribute, VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+};
+
+// Number of segments used to approximate circles and spheres
+const CIRCLE_SEGMENTS: usize = 24;
+
+// View projection and viewport size, padded to the size of the WGSL struct
+type CameraUniform = [f32; 20];
+
+// Position followed by color
+type LineVertex = [f32; 7];
+
+// Anchor, world space flag, pixel offset, atlas texel and color
+type TextVertex = [f32; 12];
+
+const LINE_ATTRIBUTES: [VertexAttribute; 2] = [
+    VertexAttribute {
+        format: VertexFormat::Float32x3,
+        offset: 0,
+        shader_location: 0,
+    },
+    VertexAttribute {
+        format: VertexFormat::Float32x4,
+        offset: 12,
+        shader_location: 1,
+    },
+];
+
+const TEXT_ATTRIBUTES: [VertexAttribute; 5] = [
+    VertexAttribute {
+        format: VertexFormat::Float32x3,
+        offset: 0,
+        shader_location: 0,
+    },
+    VertexAttribute {
+        format: VertexFormat::Float32,
+        offset: 12,
+        shader_location: 1,
+    },
+    VertexAttribute {
+        format: VertexFormat::Float32x2,
+        offset: 16,
+        shader_location: 2,
+    },
+    VertexAttribute {
+        format: VertexFormat::Float32x2,
+        offset: 24,
+        shader_location: 3,
+    },
+    VertexAttribute {
+        format: VertexFormat::Float32x4,
+        offset: 32,
+        shader_location: 4,
+    },
+];
+
+// How a debug primitive is drawn. A zero duration draws the primitive for a
+// single frame.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct DebugStyle {
+    pub color: [f32; 4],
+    pub duration: Duration,
+    pub depth_test: bool,
+}
+
+impl Default for DebugStyle {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 1.0, 1.0, 1.0],
+            duration: Duration::ZERO,
+            depth_test: true,
+        }
+    }
+}
+
+impl DebugStyle {
+    pub fn new(color: [f32; 4]) -> Self {
+        Self {
+            color,
+            ..Default::default()
+        }
+    }
+}
+
+struct DebugPrimitive {
+    line_vertices: Vec<LineVertex>,
+    text_vertices: Vec<TextVertex>,
+    depth_test: bool,
+    expires: Instant,
+    drawn: bool,
+}
+
+// A depth tested and an overlay variant of one debug pipeline
+struct DebugPipelines {
+    depth_tested: RenderPipeline,
+    overlay: RenderPipeline,
+}
+
+impl DebugPipelines {
+    fn new(
+        device: &Device,
+        label: &str,
+        source: &str,
+        bind_group_layouts: &[&BindGroupLayout],
+        vertex_buffer_layout: VertexBufferLayout,
+        topology: PrimitiveTopology,
+        format: TextureFormat,
+    ) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&format!("{}/shader", label)),
+            source: wgpu::ShaderSource::Wgsl(source.into()),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some(&format!("{}/pipeline_layout", label)),
+            bind_group_layouts,
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |depth_compare| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: std::slice::from_ref(&vertex_buffer_layout),
+                    compilation_options: PipelineCompilationOptions::default(),
+                },
+                primitive: wgpu::PrimitiveState {
+                    topology,
+                    ..Default::default()
+                },
+                // Debug geometry never occludes anything itself
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Renderer::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                    compilation_options: PipelineCompilationOptions::default(),
+                }),
+                multiview: None,
+            })
+        };
+
+        Self {
+            depth_tested: create_pipeline(CompareFunction::LessEqual),
+            overlay: create_pipeline(CompareFunction::Always),
+        }
+    }
+
+    fn get(&self, depth_test: bool) -> &RenderPipeline {
+        match depth_test {
+            true => &self.depth_tested,
+            false => &self.overlay,
+        }
+    }
+}
+
+// A buffer that the views of a frame are written into one after another with
+// queue.write_buffer, so that the views recorded earlier in the same
+// submission keep their data. The buffer doubles in size when a frame does
+// not fit.
+struct FrameBuffer {
+    label: &'static str,
+    usage: BufferUsages,
+    buffer: Buffer,
+    frame_number: Option<u64>,
+    end: u64,
+}
+
+impl FrameBuffer {
+    fn new(device: &Device, label: &'static str, usage: BufferUsages, size: u64) -> Self {
+        Self {
+            label,
+            usage,
+            buffer: Self::create_buffer(device, label, usage, size),
+            frame_number: None,
+            end: 0,
+        }
+    }
+
+    fn create_buffer(device: &Device, label: &str, usage: BufferUsages, size: u64) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some(label),
+            size,
+            usage: BufferUsages::COPY_DST | usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    // Writes the data after the data of the frame's earlier views and returns
+    // its offset, and whether the buffer was replaced by a larger one
+    fn write(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        frame_number: u64,
+        alignment: u64,
+        data: &[u8],
+    ) -> (u64, bool) {
+        if self.frame_number != Some(frame_number) {
+            self.frame_number = Some(frame_number);
+            self.end = 0;
+        }
+
+        let mut offset = self.end.div_ceil(alignment) * alignment;
+        let mut reallocated = false;
+        if offset + data.len() as u64 > self.buffer.size() {
+            // Draws recorded into the old buffer keep it alive until they
+            // have executed
+            let mut size = self.buffer.size();
+            while size < offset + data.len() as u64 {
+                size *= 2;
+            }
+            self.buffer = Self::create_buffer(device, self.label, self.usage, size);
+            offset = 0;
+            reallocated = true;
+        }
+
+        queue.write_buffer(&self.buffer, offset, data);
+        self.end = offset + data.len() as u64;
+        (offset, reallocated)
+    }
+}
+
+// Immediate mode debug drawing layered on top of the renderer. Primitives are
+// added every frame (or once with a duration), batched into a single vertex
+// buffer and drawn after the scene geometry by Renderer::draw_debug. Text uses
+// a built-in 5x7 pixel font.
+pub struct DebugDraw {
+    primitives: Vec<DebugPrimitive>,
+    // The frame last drawn into, a frame can draw several views
+    frame_number: Option<u64>,
+    view_projection: Mat4,
+    line_pipelines: DebugPipelines,
+    text_pipelines: DebugPipelines,
+    camera_layout: BindGroupLayout,
+    // One camera per view, bound with a dynamic offset
+    cameras: FrameBuffer,
+    camera_stride: u64,
+    camera_bind_group: BindGroup,
+    _font_atlas: Texture,
+    font_bind_group: BindGroup,
+    vertices: FrameBuffer,
+}
+
+impl DebugDraw {
+    pub fn new(renderer: &Renderer) -> Result<Self, RendererError> {
+        let device = renderer.device();
+        let camera_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("debug_draw/camera_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: true,
+                    min_binding_size: NonZeroU64::new(std::mem::size_of::<CameraUniform>() as u64),
+                },
+                count: None,
+            }],
+        });
+        let camera_size = std::mem::size_of::<CameraUniform>() as u64;
+        let camera_alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let camera_stride = camera_size.div_ceil(camera_alignment) * camera_alignment;
+        let cameras = FrameBuffer::new(
+            device,
+            "debug_draw/cameras",
+            BufferUsages::UNIFORM,
+            camera_stride * 4,
+        );
+        let camera_bind_group = Self::create_camera_bind_group(device, &camera_layout, &cameras);
+
+        // The atlas is read with textureLoad so no sampler is needed
+        let font_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("debug_draw/font_layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let font_atlas =
+            Texture::from_source(device, renderer.queue(), &debug_font::atlas_source())?;
+        let font_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("debug_draw/font"),
+            layout: &font_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(font_atlas.view()),
+            }],
+        });
+
+        let format = renderer.target_format();
+        let line_pipelines = DebugPipelines::new(
+            device,
+            "debug_draw/lines",
+            include_str!("shaders/debug.wgsl"),
+            &[&camera_layout],
+            VertexBufferLayout {
+                array_stride: std::mem::size_of::<LineVertex>() as u64,
+                step_mode: VertexStepMode::Vertex,
+                attributes: &LINE_ATTRIBUTES,
+            },
+            PrimitiveTopology::LineList,
+            format,
+        );
+        let text_pipelines = DebugPipelines::new(
+            device,
+            "debug_draw/text",
+            include_str!("shaders/debug_text.wgsl"),
+            &[&camera_layout, &font_layout],
+            VertexBufferLayout {
+                array_stride: std::mem::size_of::<TextVertex>() as u64,
+                step_mode: VertexStepMode::Vertex,
+                attributes: &TEXT_ATTRIBUTES,
+            },
+            PrimitiveTopology::TriangleList,
+            format,
+        );
+
+        Ok(Self {
+            primitives: Vec::new(),
+            frame_number: None,
+            view_projection: Mat4::IDENTITY,
+            line_pipelines,
+            text_pipelines,
+            camera_layout,
+            cameras,
+            camera_stride,
+            camera_bind_group,
+            _font_atlas: font_atlas,
+            font_bind_group,
+            vertices: FrameBuffer::new(
+                device,
+                "debug_draw/vertices",
+                BufferUsages::VERTEX,
+                64 * 1024,
+            ),
+        })
+    }
+
+    fn create_camera_bind_group(
+        device: &Device,
+        layout: &BindGroupLayout,
+        cameras: &FrameBuffer,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            label: Some("debug_draw/camera"),
+            layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::Buffer(BufferBinding {
+                    buffer: &cameras.buffer,
+                    offset: 0,
+                    size: NonZeroU64::new(std::mem::size_of::<CameraUniform>() as u64),
+                }),
+            }],
+        })
+    }
+
+    // The camera used to project every primitive
+    pub fn set_view_projection(&mut self, view_projection: Mat4) {
+        self.view_projection = view_projection;
+    }
+
+    pub fn clear(&mut self) {
+        self.primitives.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.primitives.is_empty()
+    }
+
+    pub fn add_line(&mut self, start: Vec3, end: Vec3, style: &DebugStyle) {
+        self.add_lines(&[(start, end)], style);
+    }
+
+    // Three axis aligned lines crossing at the center
+    pub fn add_cross(&mut self, center: Vec3, size: f32, style: &DebugStyle) {
+        let half = size * 0.5;
+        self.add_lines(
+            &[
+                (center - Vec3::X * half, center + Vec3::X * half),
+                (center - Vec3::Y * half, center + Vec3::Y * half),
+                (center - Vec3::Z * half, center + Vec3::Z * half),
+            ],
+            style,
+        );
+    }
+
+    // Circles around the three axes
+    pub fn add_sphere(&mut self, center: Vec3, radius: f32, style: &DebugStyle) {
+        let mut lines = Vec::with_capacity(CIRCLE_SEGMENTS * 3);
+        for normal in [Vec3::X, Vec3::Y, Vec3::Z] {
+            lines.extend(circle_lines(center, normal, radius));
+        }
+        self.add_lines(&lines, style);
+    }
+
+    pub fn add_circle(&mut self, center: Vec3, normal: Vec3, radius: f32, style: &DebugStyle) {
+        self.add_lines(&circle_lines(center, normal, radius), style);
+    }
+
+    // The x, y and z axes of a transform in red, green and blue. Only the
+    // duration and depth test of the style are used.
+    pub fn add_axes(&mut self, transform: Mat4, size: f32, style: &DebugStyle) {
+        let origin = transform.transform_point3(Vec3::ZERO);
+        let axes = [
+            (Vec3::X, [1.0, 0.0, 0.0, 1.0]),
+            (Vec3::Y, [0.0, 1.0, 0.0, 1.0]),
+            (Vec3::Z, [0.0, 0.0, 1.0, 1.0]),
+        ];
+        for (axis, color) in axes {
+            let end = transform.transform_point3(axis * size);
+            self.add_lines(&[(origin, end)], &DebugStyle { color, ..*style });
+        }
+    }
+
+    pub fn add_triangle(&mut self, a: Vec3, b: Vec3, c: Vec3, style: &DebugStyle) {
+        self.add_lines(&[(a, b), (b, c), (c, a)], style);
+    }
+
+    pub fn add_aabb(&mut self, min: Vec3, max: Vec3, style: &DebugStyle) {
+        let center = (min + max) * 0.5;
+        self.add_obb(center, (max - min) * 0.5, Quat::IDENTITY, style);
+    }
+
+    pub fn add_obb(
+        &mut self,
+        center: Vec3,
+        half_extents: Vec3,
+        rotation: Quat,
+        style: &DebugStyle,
+    ) {
+        // Corner i has a positive x, y or z offset when bit 0, 1 or 2 is set
+        let corner = |i: usize| {
+            let sign = |bit: usize| if i & (1 << bit) != 0 { 1.0 } else { -1.0 };
+            center + rotation * (half_extents * Vec3::new(sign(0), sign(1), sign(2)))
+        };
+
+        // Edges join corners that differ in exactly one bit
+        let mut lines = Vec::with_capacity(12);
+        for i in 0..8 {
+            for bit in 0..3 {
+                if i & (1 << bit) == 0 {
+                    lines.push((corner(i), corner(i | (1 << bit))));
+                }
+            }
+        }
+        self.add_lines(&lines, style);
+    }
+
+    // Text anchored at a world position. Characters are scale pixels per font
+    // pixel regardless of distance, and lines are broken at newlines.
+    pub fn add_text(&mut self, position: Vec3, text: &str, scale: f32, style: &DebugStyle) {
+        self.add_text_quads(position, true, text, scale, style);
+    }
+
+    // Text anchored at a pixel position from the top left of the view, always
+    // drawn over the scene
+    pub fn add_screen_text(&mut self, position: Vec2, text: &str, scale: f32, style: &DebugStyle) {
+        self.add_text_quads(
+            position.extend(0.0),
+            false,
+            text,
+            scale,
+            &DebugStyle {
+                depth_test: false,
+                ..*style
+            },
+        );
+    }
+
+    fn add_text_quads(
+        &mut self,
+        anchor: Vec3,
+        world_space: bool,
+        text: &str,
+        scale: f32,
+        style: &DebugStyle,
+    ) {
+        let world_space = if world_space { 1.0 } else { 0.0 };
+        let [r, g, b, a] = style.color;
+        let mut vertices = Vec::with_capacity(text.len() * 6);
+        let (mut column, mut line) = (0, 0);
+        for character in text.chars() {
+            if character == '\n' {
+                column = 0;
+                line += 1;
+                continue;
+            }
+
+            if !character.is_whitespace() {
+                let (cell_x, cell_y) = debug_font::glyph_cell(character);
+                let texel = Vec2::new((cell_x * CELL_WIDTH) as f32, (cell_y * CELL_HEIGHT) as f32);
+                let offset = Vec2::new((column * CELL_WIDTH) as f32, (line * CELL_HEIGHT) as f32);
+                let size = Vec2::new(GLYPH_WIDTH as f32, GLYPH_HEIGHT as f32);
+                for corner in [
+                    [0.0, 0.0],
+                    [1.0, 0.0],
+                    [1.0, 1.0],
+                    [0.0, 0.0],
+                    [1.0, 1.0],
+                    [0.0, 1.0],
+                ] {
+                    let corner = Vec2::from(corner) * size;
+                    let offset = (offset + corner) * scale;
+                    let texel = texel + corner;
+                    vertices.push([
+                        anchor.x,
+                        anchor.y,
+                        anchor.z,
+                        world_space,
+                        offset.x,
+                        offset.y,
+                        texel.x,
+                        texel.y,
+                        r,
+                        g,
+                        b,
+                        a,
+                    ]);
+                }
+            }
+            column += 1;
+        }
+
+        self.push(Vec::new(), vertices, style);
+    }
+
+    fn add_lines(&mut self, lines: &[(Vec3, Vec3)], style: &DebugStyle) {
+        let [r, g, b, a] = style.color;
+        let vertices = lines
+            .iter()
+            .flat_map(|(start, end)| [start, end])
+            .map(|p| [p.x, p.y, p.z, r, g, b, a])
+            .collect();
+        self.push(vertices, Vec::new(), style);
+    }
+
+    fn push(
+        &mut self,
+        line_vertices: Vec<LineVertex>,
+        text_vertices: Vec<TextVertex>,
+        style: &DebugStyle,
+    ) {
+        self.primitives.push(DebugPrimitive {
+            line_vertices,
+            text_vertices,
+            depth_test: style.depth_test,
+            expires: Instant::now() + style.duration,
+            drawn: false,
+        });
+    }
+
+    // Drops primitives whose duration has passed once a new frame starts,
+    // every primitive is drawn in at least one frame
+    fn expire(&mut self, frame_number: u64) {
+        if self.frame_number == Some(frame_number) {
+            return;
+        }
+
+        self.frame_number = Some(frame_number);
+        let now = Instant::now();
+        self.primitives
+            .retain(|primitive| !primitive.drawn || primitive.expires > now);
+    }
+
+    // Uploads the primitives and records them into a single pass, lines
+    // before text and depth tested before overlays. Returns the number of
+    // draws recorded.
+    pub fn record(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        target: &mut DrawTarget,
+        frame_number: u64,
+        viewport_size: Vec2,
+    ) -> usize {
+        self.expire(frame_number);
+
+        // Vertex ranges of the depth tested and overlay primitives
+        let mut line_vertices: Vec<LineVertex> = Vec::new();
+        let mut text_vertices: Vec<TextVertex> = Vec::new();
+        let mut line_ranges = Vec::with_capacity(2);
+        let mut text_ranges = Vec::with_capacity(2);
+        for depth_test in [true, false] {
+            let (line_start, text_start) = (line_vertices.len(), text_vertices.len());
+            for primitive in self.primitives.iter_mut() {
+                if primitive.depth_test == depth_test {
+                    line_vertices.extend_from_slice(&primitive.line_vertices);
+                    text_vertices.extend_from_slice(&primitive.text_vertices);
+                    primitive.drawn = true;
+                }
+            }
+            line_ranges.push((depth_test, line_start as u32..line_vertices.len() as u32));
+            text_ranges.push((depth_test, text_start as u32..text_vertices.len() as u32));
+        }
+
+        if line_vertices.is_empty() && text_vertices.is_empty() {
+            return 0;
+        }
+
+        let line_bytes: &[u8] = bytemuck::cast_slice(&line_vertices);
+        let text_bytes: &[u8] = bytemuck::cast_slice(&text_vertices);
+
+        let mut camera: CameraUniform = [0.0; 20];
+        camera[..16].copy_from_slice(&self.view_projection.to_cols_array());
+        camera[16..18].copy_from_slice(&viewport_size.to_array());
+        let (camera_offset, reallocated) = self.cameras.write(
+            device,
+            queue,
+            frame_number,
+            self.camera_stride,
+            bytemuck::cast_slice(&camera),
+        );
+        if reallocated {
+            self.camera_bind_group =
+                Self::create_camera_bind_group(device, &self.camera_layout, &self.cameras);
+        }
+        let (line_start, _) = self.vertices.write(
+            device,
+            queue,
+            frame_number,
+            wgpu::VERTEX_STRIDE_ALIGNMENT,
+            &[line_bytes, text_bytes].concat(),
+        );
+        let line_end = line_start + line_bytes.len() as u64;
+        let text_end = line_end + text_bytes.len() as u64;
+
+        let mut render_pass = target.begin_render_pass(encoder, "debug_draw");
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[camera_offset as u32]);
+
+        let mut draws = 0;
+        if !line_bytes.is_empty() {
+            render_pass.set_vertex_buffer(0, self.vertices.buffer.slice(line_start..line_end));
+            for (depth_test, range) in line_ranges.into_iter().filter(|(_, r)| !r.is_empty()) {
+                render_pass.set_pipeline(self.line_pipelines.get(depth_test));
+                render_pass.draw(range, 0..1);
+                draws += 1;
+            }
+        }
+        if !text_bytes.is_empty() {
+            // Only the text pipelines have the font group
+            render_pass.set_bind_group(1, &self.font_bind_group, &[]);
+            render_pass.set_vertex_buffer(0, self.vertices.buffer.slice(line_end..text_end));
+            for (depth_test, range) in text_ranges.into_iter().filter(|(_, r)| !r.is_empty()) {
+                render_pass.set_pipeline(self.text_pipelines.get(depth_test));
+                render_pass.draw(range, 0..1);
+                draws += 1;
+            }
+        }
+        draws
+    }
+}
+
+fn circle_lines(center: Vec3, normal: Vec3, radius: f32) -> Vec<(Vec3, Vec3)> {
+    let (u, v) = normal.normalize_or_zero().any_orthonormal_pair();
+    let point = |i: usize| {
+        let angle = TAU * i as f32 / CIRCLE_SEGMENTS as f32;
+        center + (u * angle.cos() + v * angle.sin()) * radius
+    };
+    (0..CIRCLE_SEGMENTS)
+        .map(|i| (point(i), point(i + 1)))
+        .collect()
+}
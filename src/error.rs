@@ -0,0 +1,191 @@
+use crate::{frame::Viewport, renderer_configuration::RendererConfigurationBuilderError};
+use std::fmt::{Display, Formatter};
+use wgpu::{
+    BufferAsyncError, CompositeAlphaMode, CreateSurfaceError, PresentMode, RequestDeviceError,
+    TextureFormat,
+};
+
+#[derive(Debug)]
+pub enum RendererError {
+    Configuration(RendererConfigurationBuilderError),
+    AdapterRequest,
+    DeviceRequest(RequestDeviceError),
+    SurfaceCreation(CreateSurfaceError),
+    IncompatibleSurface,
+    UnsupportedPresentMode(PresentMode),
+    UnsupportedAlphaMode(CompositeAlphaMode),
+    SurfaceLost,
+    SurfaceOutdated,
+    SurfaceTimeout,
+    OutOfMemory,
+    ShaderLoad { path: String, error: std::io::Error },
+    ShaderCompile(ShaderError),
+    PipelineCreation { label: String, message: String },
+    ShaderReflection(ShaderError),
+    BindGroupMismatch { group: u32, message: String },
+    UnknownPipeline,
+    PipelineHasMeshes,
+    UnknownMaterial,
+    UnknownUniform,
+    UniformSize { expected: usize, actual: usize },
+    UniformTooLarge { size: usize, limit: u32 },
+    UnsupportedTextureFormat(TextureFormat),
+    InvalidHandle,
+    InvalidViewport(Viewport),
+    RenderingPaused,
+    FrameSkipped,
+    CaptureUnsupported,
+    BufferMap(BufferAsyncError),
+}
+
+impl Display for RendererError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RendererError::Configuration(e) => write!(f, "Invalid renderer configuration: {}", e),
+            RendererError::AdapterRequest => write!(f, "Failed to acquire graphics adapter"),
+            RendererError::DeviceRequest(e) => {
+                write!(f, "Failed to acquire graphics device: {}", e)
+            }
+            RendererError::SurfaceCreation(e) => write!(f, "Failed to create surface: {}", e),
+            RendererError::IncompatibleSurface => {
+                write!(f, "Failed to acquire compatible surface")
+            }
+            RendererError::UnsupportedPresentMode(mode) => {
+                write!(f, "The surface does not support present mode {:?}", mode)
+            }
+            RendererError::UnsupportedAlphaMode(mode) => {
+                write!(f, "The surface does not support alpha mode {:?}", mode)
+            }
+            RendererError::SurfaceLost => write!(f, "The surface was lost"),
+            RendererError::SurfaceOutdated => write!(f, "The surface is outdated"),
+            RendererError::SurfaceTimeout => write!(f, "Timed out acquiring the next frame"),
+            RendererError::OutOfMemory => write!(f, "The graphics device is out of memory"),
+            RendererError::ShaderLoad { path, error } => {
+                write!(f, "Failed to load shader {}: {}", path, error)
+            }
+            RendererError::ShaderCompile(error) => {
+                write!(f, "Failed to compile shader {}", error)
+            }
+            RendererError::PipelineCreation { label, message } => {
+                write!(f, "Failed to create {}: {}", label, message)
+            }
+            RendererError::ShaderReflection(error) => {
+                write!(f, "Failed to reflect shader {}", error)
+            }
+            RendererError::BindGroupMismatch { group, message } => {
+                write!(
+                    f,
+                    "Bind group {} does not match the shader: {}",
+                    group, message
+                )
+            }
+            RendererError::UnknownPipeline => write!(f, "No such pipeline is registered"),
+            RendererError::PipelineHasMeshes => {
+                write!(
+                    f,
+                    "Pipeline globals must be added before meshes are registered"
+                )
+            }
+            RendererError::UnknownMaterial => write!(f, "No such material is registered"),
+            RendererError::UnknownUniform => write!(f, "No such uniform exists"),
+            RendererError::UniformSize { expected, actual } => {
+                write!(
+                    f,
+                    "The uniform holds {} bytes but {} were provided",
+                    expected, actual
+                )
+            }
+            RendererError::UniformTooLarge { size, limit } => {
+                write!(
+                    f,
+                    "The buffer holds {} bytes but its binding type allows at most {}",
+                    size, limit
+                )
+            }
+            RendererError::UnsupportedTextureFormat(format) => {
+                write!(f, "Unsupported texture format: {:?}", format)
+            }
+            RendererError::InvalidHandle => write!(f, "The handle does not refer to a resource"),
+            RendererError::InvalidViewport(viewport) => {
+                write!(f, "The viewport lies outside the target: {:?}", viewport)
+            }
+            RendererError::RenderingPaused => write!(f, "Rendering is paused"),
+            RendererError::FrameSkipped => {
+                write!(f, "The frame is skipped, no surface texture was available")
+            }
+            RendererError::CaptureUnsupported => {
+                write!(f, "The color target can not be copied from")
+            }
+            RendererError::BufferMap(e) => write!(f, "Failed to map buffer: {}", e),
+        }
+    }
+}
+
+// A shader that failed to parse or validate. The file is the path of the
+// shader, or the label of its module for inline sources, and the line and
+// column are 1-based and only known for errors that point into the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShaderError {
+    pub file: String,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl ShaderError {
+    pub(crate) fn new(file: &str, message: String) -> Self {
+        Self {
+            file: file.to_string(),
+            line: None,
+            column: None,
+            message,
+        }
+    }
+}
+
+impl Display for ShaderError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.file)?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        if let Some(column) = self.column {
+            write!(f, ":{}", column)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl std::error::Error for RendererError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RendererError::Configuration(e) => Some(e),
+            RendererError::DeviceRequest(e) => Some(e),
+            RendererError::SurfaceCreation(e) => Some(e),
+            RendererError::ShaderLoad { error, .. } => Some(error),
+            RendererError::ShaderCompile(error) => Some(error),
+            RendererError::ShaderReflection(error) => Some(error),
+            RendererError::BufferMap(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<RendererConfigurationBuilderError> for RendererError {
+    fn from(error: RendererConfigurationBuilderError) -> Self {
+        RendererError::Configuration(error)
+    }
+}
+
+impl From<wgpu::SurfaceError> for RendererError {
+    fn from(error: wgpu::SurfaceError) -> Self {
+        match error {
+            wgpu::SurfaceError::Lost => RendererError::SurfaceLost,
+            wgpu::SurfaceError::Outdated => RendererError::SurfaceOutdated,
+            wgpu::SurfaceError::Timeout => RendererError::SurfaceTimeout,
+            wgpu::SurfaceError::OutOfMemory => RendererError::OutOfMemory,
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use wgpu::{
+    Color, CommandEncoder, LoadOp, Operations, RenderPass, RenderPassColorAttachment,
+    RenderPassDepthStencilAttachment, RenderPassDescriptor, StoreOp, TextureView,
+};
+
+// A region of the color and depth targets in pixels, used to draw several
+// views (split screen, picture in picture) into one frame
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Viewport {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub min_depth: f32,
+    pub max_depth: f32,
+}
+
+impl Viewport {
+    pub fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }
+    }
+}
+
+// Returned by Renderer::begin_frame and handed back to Renderer::end_frame.
+// The surface texture is only acquired once the frame first draws, so it is
+// held for as short a time as possible.
+#[must_use = "a frame must be passed to Renderer::end_frame to be presented"]
+pub struct Frame {
+    pub(crate) number: u64,
+    pub(crate) viewport: Option<Viewport>,
+    // Set when no surface texture could be acquired, every draw is dropped
+    pub(crate) skipped: bool,
+}
+
+impl Frame {
+    pub(crate) fn new(number: u64) -> Self {
+        Self {
+            number,
+            viewport: None,
+            skipped: false,
+        }
+    }
+
+    // Counts the frames begun by the renderer
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+
+    // None covers the whole target
+    pub fn viewport(&self) -> Option<Viewport> {
+        self.viewport
+    }
+
+    pub fn is_skipped(&self) -> bool {
+        self.skipped
+    }
+}
+
+// The attachments and viewport that queued draws are recorded into
+pub struct DrawTarget<'a> {
+    pub color: &'a TextureView,
+    pub depth: &'a TextureView,
+    pub viewport: Option<Viewport>,
+    // Set until a pass has cleared the target, which is folded into the first
+    // pass of the frame rather than recorded on its own
+    pub clear_pending: bool,
+    pub clear_color: Option<Color>,
+    pub clear_depth: Option<f32>,
+}
+
+impl<'a> DrawTarget<'a> {
+    pub fn begin_render_pass<'e>(
+        &mut self,
+        encoder: &'e mut CommandEncoder,
+        label: &str,
+    ) -> RenderPass<'e>
+    where
+        'a: 'e,
+    {
+        let (color_load, depth_load) = match std::mem::take(&mut self.clear_pending) {
+            true => (
+                self.clear_color.map_or(LoadOp::Load, LoadOp::Clear),
+                self.clear_depth.map_or(LoadOp::Load, LoadOp::Clear),
+            ),
+            false => (LoadOp::Load, LoadOp::Load),
+        };
+
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: self.color,
+                resolve_target: None,
+                ops: Operations {
+                    load: color_load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+                view: self.depth,
+                depth_ops: Some(Operations {
+                    load: depth_load,
+                    store: StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        if let Some(viewport) = self.viewport {
+            render_pass.set_viewport(
+                viewport.x,
+                viewport.y,
+                viewport.width,
+                viewport.height,
+                viewport.min_depth,
+                viewport.max_depth,
+            );
+        }
+        render_pass
+    }
+}
@@ -0,0 +1,96 @@
+use crate::{error::RendererError, texture::Texture};
+use image::RgbaImage;
+use std::sync::mpsc;
+use wgpu::{
+    BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoderDescriptor, Device, Extent3d,
+    Maintain, MapMode, Queue, TextureFormat, TextureUsages,
+};
+
+// Copies a color texture into a mappable buffer and reads it back into an
+// RGBA image. Only 8 bit RGBA and BGRA formats can be captured, from textures
+// created with the COPY_SRC usage, which not every surface supports.
+pub fn capture_texture(
+    device: &Device,
+    queue: &Queue,
+    texture: &wgpu::Texture,
+    format: TextureFormat,
+) -> Result<RgbaImage, RendererError> {
+    if !texture.usage().contains(TextureUsages::COPY_SRC) {
+        return Err(RendererError::CaptureUnsupported);
+    }
+
+    let swap_red_blue = match format {
+        TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+        TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+        format => return Err(RendererError::UnsupportedTextureFormat(format)),
+    };
+
+    let width = texture.width();
+    let height = texture.height();
+    let unpadded_bytes_per_row = Texture::bytes_per_row(width, &format)?;
+    let padded_bytes_per_row = Texture::padded_bytes_per_row(width, &format)?;
+
+    let buffer = device.create_buffer(&BufferDescriptor {
+        label: Some("frame_capture"),
+        size: padded_bytes_per_row as u64 * height as u64,
+        usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+        label: Some("frame_capture"),
+    });
+    encoder.copy_texture_to_buffer(
+        wgpu::ImageCopyTexture {
+            aspect: wgpu::TextureAspect::All,
+            texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+        },
+        wgpu::ImageCopyBuffer {
+            buffer: &buffer,
+            layout: wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_bytes_per_row),
+                rows_per_image: Some(height),
+            },
+        },
+        Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        },
+    );
+    queue.submit([encoder.finish()]);
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    device.poll(Maintain::Wait);
+    receiver
+        .recv()
+        .map_err(|_| RendererError::BufferMap(BufferAsyncError))?
+        .map_err(RendererError::BufferMap)?;
+
+    // Strip the row padding required by the copy
+    let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * height) as usize);
+    {
+        let mapped = slice.get_mapped_range();
+        for row in mapped.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+    }
+    buffer.unmap();
+
+    if swap_red_blue {
+        for pixel in pixels.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+
+    // The rows only come up short for formats bytes_per_row gets wrong
+    RgbaImage::from_raw(width, height, pixels)
+        .ok_or(RendererError::UnsupportedTextureFormat(format))
+}
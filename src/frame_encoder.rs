@@ -0,0 +1,116 @@
+use crate::submission_policy::SubmissionPolicy;
+use std::num::NonZeroU64;
+use wgpu::{util::StagingBelt, Buffer, CommandEncoder, CommandEncoderDescriptor, Device, Queue};
+
+// Size of the chunks buffer writes are staged in, larger writes get a chunk
+// of their own
+const STAGING_CHUNK_SIZE: u64 = 1 << 16;
+
+// Counters for the work recorded during one frame
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct FrameStatistics {
+    pub submissions: u32,
+    pub render_passes: u32,
+    pub draws: u32,
+}
+
+// Holds the command encoder of the current frame and submits it as the
+// submission policy decides
+pub struct FrameEncoder {
+    policy: Box<dyn SubmissionPolicy>,
+    encoder: Option<CommandEncoder>,
+    // Staging memory for buffer writes ordered with the recorded work, reused
+    // once the submissions that copied from it are done
+    staging_belt: StagingBelt,
+    pending_passes: u32,
+    statistics: FrameStatistics,
+}
+
+impl FrameEncoder {
+    pub fn new(policy: Box<dyn SubmissionPolicy>) -> Self {
+        Self {
+            policy,
+            encoder: None,
+            staging_belt: StagingBelt::new(STAGING_CHUNK_SIZE),
+            pending_passes: 0,
+            statistics: FrameStatistics::default(),
+        }
+    }
+
+    pub fn policy(&self) -> &dyn SubmissionPolicy {
+        self.policy.as_ref()
+    }
+
+    pub fn set_policy(&mut self, policy: Box<dyn SubmissionPolicy>) {
+        self.policy = policy;
+    }
+
+    pub fn should_record(&self, queued_draws: usize) -> bool {
+        self.policy.should_record(queued_draws)
+    }
+
+    pub fn encoder(&mut self, device: &Device) -> &mut CommandEncoder {
+        self.encoder.get_or_insert_with(|| {
+            device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("frame"),
+            })
+        })
+    }
+
+    // Replaces the contents of a buffer. While work is recorded but not yet
+    // submitted the copy is staged into the encoder, so that work recorded
+    // earlier still sees the old contents and work recorded later the new
+    // ones. Otherwise the write happens before the next submission.
+    pub fn write_buffer(&mut self, device: &Device, queue: &Queue, buffer: &Buffer, data: &[u8]) {
+        let (Some(encoder), Some(size)) =
+            (self.encoder.as_mut(), NonZeroU64::new(data.len() as u64))
+        else {
+            queue.write_buffer(buffer, 0, data);
+            return;
+        };
+        self.staging_belt
+            .write_buffer(encoder, buffer, 0, size, device)
+            .copy_from_slice(data);
+    }
+
+    // Called after a render pass with the given number of draws is recorded
+    pub fn end_pass(&mut self, queue: &Queue, draws: usize) {
+        self.statistics.render_passes += 1;
+        self.statistics.draws += draws as u32;
+        self.pending_passes += 1;
+        if self.policy.should_submit(self.pending_passes) {
+            self.submit(queue);
+        }
+    }
+
+    // Submits everything recorded so far
+    pub fn submit(&mut self, queue: &Queue) {
+        if let Some(encoder) = self.encoder.take() {
+            // Staged writes have to be unmapped before the copies run
+            self.staging_belt.finish();
+            queue.submit([encoder.finish()]);
+            self.staging_belt.recall();
+            self.statistics.submissions += 1;
+            self.pending_passes = 0;
+        }
+    }
+
+    // Drops the work recorded for a frame that is skipped, it would draw
+    // into a texture that is never presented
+    pub fn discard(&mut self) {
+        self.encoder = None;
+        self.staging_belt.finish();
+        self.staging_belt.recall();
+        self.pending_passes = 0;
+        self.statistics = FrameStatistics::default();
+    }
+
+    // Returns the statistics of the frame that just ended and starts counting
+    // the next one
+    pub fn end_frame(&mut self, queue: &Queue) -> FrameStatistics {
+        self.submit(queue);
+        let statistics = std::mem::take(&mut self.statistics);
+        self.policy.end_frame(&statistics);
+        statistics
+    }
+}
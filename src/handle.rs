@@ -0,0 +1,106 @@
+use crate::{material::Material, mesh::Mesh, pipeline::Pipeline};
+use slot_map::{SlotMap, SlotMapIndex};
+use std::{
+    fmt::{Debug, Formatter},
+    hash::{Hash, Hasher},
+    marker::PhantomData,
+};
+
+pub type MeshHandle = Handle<Mesh>;
+pub type MaterialHandle = Handle<Material>;
+pub type PipelineHandle = Handle<Pipeline>;
+
+// A typed reference to a resource stored in a HandleMap. Every insertion is
+// given a new generation so a handle to a removed resource never resolves to
+// whatever later reuses its slot.
+pub struct Handle<T> {
+    index: SlotMapIndex,
+    generation: u64,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> PartialEq for Handle<T> {
+    fn eq(&self, other: &Self) -> bool {
+        // Generations are unique within a map so they identify the resource
+        self.generation == other.generation
+    }
+}
+
+impl<T> Eq for Handle<T> {}
+
+impl<T> Hash for Handle<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.generation.hash(state);
+    }
+}
+
+impl<T> Debug for Handle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Handle")
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+pub struct HandleMap<T> {
+    items: SlotMap<(u64, T)>,
+    next_generation: u64,
+}
+
+impl<T> HandleMap<T> {
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            items: SlotMap::with_capacity(capacity),
+            next_generation: 0,
+        }
+    }
+
+    pub fn insert(&mut self, item: T) -> Handle<T> {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        let index = self.items.push((generation, item));
+        Handle {
+            index,
+            generation,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn contains(&self, handle: &Handle<T>) -> bool {
+        self.get(handle).is_some()
+    }
+
+    pub fn get(&self, handle: &Handle<T>) -> Option<&T> {
+        match self.items.get(&handle.index) {
+            Some((generation, item)) if *generation == handle.generation => Some(item),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, handle: &Handle<T>) -> Option<&mut T> {
+        match self.items.get_mut(&handle.index) {
+            Some((generation, item)) if *generation == handle.generation => Some(item),
+            _ => None,
+        }
+    }
+
+    pub fn remove(&mut self, handle: &Handle<T>) -> Option<T> {
+        if !self.contains(handle) {
+            return None;
+        }
+
+        self.items.remove(&handle.index).map(|(_, item)| item)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        (&mut self.items).into_iter().map(|(_, item)| item)
+    }
+}
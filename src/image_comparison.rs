@@ -0,0 +1,105 @@
+use image::{Rgba, RgbaImage};
+
+#[derive(Debug, Clone)]
+pub struct ImageComparison {
+    pub mismatched_pixels: usize,
+    pub max_channel_difference: u8,
+    pub diff: RgbaImage,
+}
+
+impl ImageComparison {
+    pub fn matches(&self) -> bool {
+        self.mismatched_pixels == 0
+    }
+}
+
+// Compares two images. The diff image marks mismatched pixels in red over a
+// dimmed copy of the expected image, and every pixel mismatches when the
+// sizes differ.
+pub fn compare_images(actual: &RgbaImage, expected: &RgbaImage, tolerance: u8) -> ImageComparison {
+    if actual.dimensions() != expected.dimensions() {
+        return ImageComparison {
+            mismatched_pixels: (expected.width() * expected.height()) as usize,
+            max_channel_difference: u8::MAX,
+            diff: RgbaImage::from_pixel(
+                expected.width(),
+                expected.height(),
+                Rgba([255, 0, 0, 255]),
+            ),
+        };
+    }
+
+    let mut mismatched_pixels = 0;
+    let mut max_channel_difference = 0;
+    let mut diff = RgbaImage::new(expected.width(), expected.height());
+
+    for (x, y, expected_pixel) in expected.enumerate_pixels() {
+        let actual_pixel = actual.get_pixel(x, y);
+        let difference = expected_pixel
+            .0
+            .iter()
+            .zip(actual_pixel.0.iter())
+            .map(|(e, a)| e.abs_diff(*a))
+            .max()
+            .unwrap_or(0);
+
+        max_channel_difference = max_channel_difference.max(difference);
+        if difference > tolerance {
+            mismatched_pixels += 1;
+            diff.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+        } else {
+            let [r, g, b, _] = expected_pixel.0;
+            diff.put_pixel(x, y, Rgba([r / 4, g / 4, b / 4, 255]));
+        }
+    }
+
+    ImageComparison {
+        mismatched_pixels,
+        max_channel_difference,
+        diff,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn differences_within_tolerance_match() {
+        let expected = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+        let mut actual = expected.clone();
+        actual.put_pixel(1, 2, Rgba([102, 100, 99, 255]));
+
+        let comparison = compare_images(&actual, &expected, 2);
+        assert!(comparison.matches());
+        assert_eq!(comparison.max_channel_difference, 2);
+        assert_eq!(comparison.diff.get_pixel(1, 2).0, [25, 25, 25, 255]);
+    }
+
+    #[test]
+    fn mismatched_pixels_are_marked() {
+        let expected = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+        let mut actual = expected.clone();
+        actual.put_pixel(0, 0, Rgba([100, 100, 100, 0]));
+        actual.put_pixel(3, 3, Rgba([110, 100, 100, 255]));
+
+        let comparison = compare_images(&actual, &expected, 2);
+        assert!(!comparison.matches());
+        assert_eq!(comparison.mismatched_pixels, 2);
+        assert_eq!(comparison.max_channel_difference, 255);
+        assert_eq!(comparison.diff.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(comparison.diff.get_pixel(3, 3).0, [255, 0, 0, 255]);
+        assert_eq!(comparison.diff.get_pixel(1, 1).0, [25, 25, 25, 255]);
+    }
+
+    #[test]
+    fn images_of_different_sizes_mismatch() {
+        let expected = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+        let actual = RgbaImage::from_pixel(2, 4, Rgba([100, 100, 100, 255]));
+
+        let comparison = compare_images(&actual, &expected, 2);
+        assert!(!comparison.matches());
+        assert_eq!(comparison.mismatched_pixels, 16);
+        assert_eq!(comparison.diff.dimensions(), (4, 4));
+    }
+}
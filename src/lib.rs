@@ -1,26 +1,69 @@
+mod bind_group_layout_cache;
+mod debug_draw;
+mod debug_font;
+mod error;
+mod frame;
+mod frame_capture;
+mod frame_encoder;
+mod handle;
+#[cfg(any(test, feature = "testing"))]
+mod image_comparison;
 mod material;
 mod material_cache;
 mod mesh;
 mod pipeline;
 mod pipeline_configuration;
+mod reflection;
+mod render_target;
 mod renderer;
 mod renderer_configuration;
+mod retirement_queue;
+mod shader_library;
+mod shader_source;
 mod sub_mesh;
+mod submission_policy;
+#[cfg(feature = "testing")]
+pub mod testing;
 mod texture;
+mod typed_uniform;
 mod uniform;
 mod uniform_group;
 
+pub use debug_draw::{DebugDraw, DebugStyle};
+pub use error::{RendererError, ShaderError};
+pub use frame::{Frame, Viewport};
+pub use frame_encoder::FrameStatistics;
+pub use handle::{Handle, MaterialHandle, MeshHandle, PipelineHandle};
 pub use material::MaterialSource;
 pub use mesh::MeshSource;
-pub use pipeline_configuration::PipelineConfiguration;
+pub use pipeline_configuration::{PipelineConfiguration, VertexBufferLayoutSource};
+pub use renderer::FrameStatus;
 pub use renderer::Renderer;
 pub use renderer_configuration::RendererConfiguration;
 pub use renderer_configuration::RendererConfigurationBuilder;
+pub use renderer_configuration::RendererConfigurationBuilderError;
+pub use shader_source::ShaderSource;
 pub use sub_mesh::SubMeshSource;
+pub use submission_policy::{AdaptiveTarget, FixedCount, OncePerFrame, SubmissionPolicy};
 pub use texture::TextureSource;
+#[doc(hidden)]
+pub use typed_uniform::WgslField;
+pub use typed_uniform::{TypedUniform, WgslType};
 pub use uniform::UniformSource;
 pub use uniform_group::UniformGroupSource;
 
 // Re-exports
 
+pub use bytemuck;
+#[cfg(feature = "glsl")]
+pub use wgpu::naga::ShaderStage;
+pub use wgpu::AdapterInfo;
+pub use wgpu::Backends;
+pub use wgpu::BufferBindingType;
+pub use wgpu::Color;
+pub use wgpu::CompositeAlphaMode;
+pub use wgpu::Features;
 pub use wgpu::InstanceFlags;
+pub use wgpu::Limits;
+pub use wgpu::PowerPreference;
+pub use wgpu::PresentMode;
@@ -1,22 +1,28 @@
-use crate::material::Material;
-use std::collections::HashMap;
+use crate::{
+    handle::{HandleMap, MaterialHandle},
+    material::Material,
+};
 
 pub struct MaterialCache {
-    cache: HashMap<u64, Material>,
+    cache: HandleMap<Material>,
 }
 
 impl MaterialCache {
     pub fn new() -> Self {
         Self {
-            cache: HashMap::new(),
+            cache: HandleMap::with_capacity(12),
         }
     }
 
-    pub fn insert(&mut self, id: u64, material: Material) -> Option<Material> {
-        self.cache.insert(id, material)
+    pub fn insert(&mut self, material: Material) -> MaterialHandle {
+        self.cache.insert(material)
     }
 
-    pub fn get(&self, id: &u64) -> Option<&Material> {
-        self.cache.get(id)
+    pub fn remove(&mut self, handle: &MaterialHandle) -> Option<Material> {
+        self.cache.remove(handle)
+    }
+
+    pub fn get(&self, handle: &MaterialHandle) -> Option<&Material> {
+        self.cache.get(handle)
     }
 }
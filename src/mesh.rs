@@ -1,60 +1,112 @@
 use crate::{
+    bind_group_layout_cache::BindGroupLayoutCache,
+    error::RendererError,
+    handle::{HandleMap, MaterialHandle, PipelineHandle},
+    material::Material,
     material_cache::MaterialCache,
-    pipeline_configuration::PipelineConfiguration,
+    pipeline::Pipeline,
     sub_mesh::{SubMesh, SubMeshSource},
     uniform_group::UniformGroup,
     UniformGroupSource,
 };
-use slot_map::SlotMapIndex;
-use std::collections::HashMap;
 use wgpu::{Device, RenderPass};
 
-pub type MeshHandle = SlotMapIndex;
-
 pub struct Mesh {
-    name: String,
     sub_meshes: Vec<SubMesh>,
     mesh_uniform_group: UniformGroup,
-    pipeline: SlotMapIndex,
+    pipeline: PipelineHandle,
 }
 
 pub struct MeshSource {
     pub name: String,
     pub sub_meshes: Vec<SubMeshSource>,
     pub mesh_uniform_group: UniformGroupSource,
-    pub pipeline_configuration: PipelineConfiguration,
+    pub pipeline: PipelineHandle,
 }
 
 impl Mesh {
     pub fn from_source(
         device: &Device,
-        pipeline_lookup: &HashMap<PipelineConfiguration, SlotMapIndex>,
+        pipelines: &HandleMap<Pipeline>,
+        material_cache: &MaterialCache,
+        layouts: &mut BindGroupLayoutCache,
         source: &MeshSource,
-    ) -> Self {
-        let mut sub_meshes = Vec::new();
+    ) -> Result<Self, RendererError> {
+        let pipeline = pipelines
+            .get(&source.pipeline)
+            .ok_or(RendererError::UnknownPipeline)?;
+
+        // Mesh groups are bound after the pipeline globals, which can not be
+        // added once the pipeline has meshes
+        let mesh_group = pipeline.global_group_count();
+        let layout_entries = pipeline.uniform_group_layout(mesh_group, &source.mesh_uniform_group);
+        pipeline.validate_group(mesh_group, &layout_entries)?;
         for sub_mesh in &source.sub_meshes {
-            let sub_mesh = SubMesh::from_source(device, sub_mesh);
+            let material = material_cache
+                .get(&sub_mesh.material)
+                .ok_or(RendererError::UnknownMaterial)?;
+            pipeline.validate_group(mesh_group + 1, &material.layout_entries())?;
+        }
+
+        let mut sub_meshes = Vec::new();
+        for (index, sub_mesh) in source.sub_meshes.iter().enumerate() {
+            let label = format!("{}/sub_mesh({})", source.name, index);
+            let sub_mesh = SubMesh::from_source(device, &label, sub_mesh);
             sub_meshes.push(sub_mesh);
         }
 
-        Self {
-            name: source.name.clone(),
+        Ok(Self {
             sub_meshes,
-            mesh_uniform_group: UniformGroup::from_source(&source.mesh_uniform_group, device),
-            pipeline: *pipeline_lookup.get(&source.pipeline_configuration).unwrap(),
-        }
+            mesh_uniform_group: UniformGroup::from_source(
+                &source.mesh_uniform_group,
+                &layout_entries,
+                device,
+                layouts,
+            )?,
+            pipeline: source.pipeline,
+        })
     }
 
-    pub fn pipeline(&self) -> &SlotMapIndex {
+    pub fn pipeline(&self) -> &PipelineHandle {
         &self.pipeline
     }
 
+    pub fn mesh_uniform_group(&self) -> &UniformGroup {
+        &self.mesh_uniform_group
+    }
+
+    pub fn uses_material(&self, material: &MaterialHandle) -> bool {
+        self.sub_meshes
+            .iter()
+            .any(|sub_mesh| sub_mesh.material() == material)
+    }
+
+    // The registered materials the mesh draws with
+    pub fn materials<'a>(
+        &'a self,
+        material_cache: &'a MaterialCache,
+    ) -> impl Iterator<Item = &'a Material> {
+        self.sub_meshes
+            .iter()
+            .filter_map(|sub_mesh| material_cache.get(sub_mesh.material()))
+    }
+
+    // Checks that every material the mesh draws with is still registered
+    pub fn validate_materials(&self, material_cache: &MaterialCache) -> Result<(), RendererError> {
+        for sub_mesh in &self.sub_meshes {
+            material_cache
+                .get(sub_mesh.material())
+                .ok_or(RendererError::UnknownMaterial)?;
+        }
+        Ok(())
+    }
+
     pub fn record_commands<'a>(
         &'a self,
         render_pass: &mut RenderPass<'a>,
         mut first_available_bind_group: u32,
         material_cache: &'a MaterialCache,
-    ) {
+    ) -> Result<(), RendererError> {
         render_pass.set_bind_group(
             first_available_bind_group,
             self.mesh_uniform_group.bind_group(),
@@ -62,7 +114,8 @@ impl Mesh {
         );
         first_available_bind_group += 1;
         for sub_mesh in &self.sub_meshes {
-            sub_mesh.record_commands(render_pass, first_available_bind_group, material_cache);
+            sub_mesh.record_commands(render_pass, first_available_bind_group, material_cache)?;
         }
+        Ok(())
     }
 }
@@ -1,23 +1,47 @@
 use crate::{
+    bind_group_layout_cache::BindGroupLayoutCache,
+    error::RendererError,
+    frame::DrawTarget,
+    handle::{HandleMap, MeshHandle},
     material_cache::MaterialCache,
-    mesh::{Mesh, MeshHandle},
-    pipeline_configuration::PipelineConfiguration,
+    mesh::Mesh,
+    pipeline_configuration::{PipelineConfiguration, VertexBufferLayoutSource},
+    reflection,
+    shader_source::ShaderSource,
     uniform_group::{UniformGroup, UniformGroupSource},
     Renderer,
 };
-use slot_map::SlotMap;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::SystemTime,
+};
 use wgpu::{
-    BindGroupLayout, BindGroupLayoutDescriptor, CommandEncoderDescriptor, Device,
-    PipelineCompilationOptions, PipelineLayoutDescriptor, Queue, RenderPassDescriptor,
-    RenderPipeline, RenderPipelineDescriptor, StoreOp, TextureFormat, TextureView, VertexState,
+    naga::{Module, ShaderStage},
+    BindGroupLayout, BindGroupLayoutEntry, CommandEncoder, Device, ErrorFilter,
+    PipelineCompilationOptions, PipelineLayoutDescriptor, RenderPipeline, RenderPipelineDescriptor,
+    TextureFormat, VertexBufferLayout, VertexState,
 };
 
 pub struct Pipeline {
     pipeline: RenderPipeline,
     configuration: PipelineConfiguration,
-    bind_group_layouts: Vec<BindGroupLayout>,
+    bind_group_layouts: Vec<Arc<BindGroupLayout>>,
     global_bind_groups: Vec<UniformGroup>,
+    // Registered meshes drawn with the pipeline, which fix the group index
+    // of the mesh groups
+    meshes: Vec<MeshHandle>,
     draw_queue: Vec<MeshHandle>,
+    // Shader files and their modification times when last compiled
+    shader_files: Vec<(PathBuf, Option<SystemTime>)>,
+    // Bind group layouts declared by WGSL shaders, None for other sources
+    reflected_layouts: Option<Vec<Vec<BindGroupLayoutEntry>>>,
+}
+
+struct CompiledPipeline {
+    pipeline: RenderPipeline,
+    bind_group_layouts: Vec<Arc<BindGroupLayout>>,
+    reflected_layouts: Option<Vec<Vec<BindGroupLayoutEntry>>>,
 }
 
 impl Pipeline {
@@ -25,190 +49,383 @@ impl Pipeline {
         configuration: PipelineConfiguration,
         device: &wgpu::Device,
         surface_format: &TextureFormat,
-    ) -> Result<Self, String> {
-        let label = format!("pipeline({})", configuration.shader_path);
-        let mut bind_group_layouts = Vec::new();
-        for (i, bind_group_layout) in configuration.bind_group_layouts.iter().enumerate() {
-            let label = format!("{}/bind_group_layout({})", label, i);
-            let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-                entries: bind_group_layout,
-                label: Some(&label),
-            });
-            bind_group_layouts.push(layout);
-        }
-
-        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-            label: Some(&format!("{}/render_pipeline_layout", label)),
-            bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
-            push_constant_ranges: &[],
-        });
-
-        let shader_source = std::fs::read_to_string(configuration.shader_path.clone())
-            .map_err(|e| e.to_string())?;
-
-        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: Some(&format!("{}/vertex_shader", label)),
-            source: wgpu::ShaderSource::Wgsl(shader_source.into()),
-        });
-
-        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some(&label),
-            layout: Some(&pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: &configuration.vertex_shader_entrypoint,
-                buffers: &configuration.vertex_buffer_layouts,
-                compilation_options: PipelineCompilationOptions::default(),
-            },
-            primitive: wgpu::PrimitiveState {
-                topology: configuration.topology,
-                strip_index_format: configuration.strip_index_format,
-                front_face: configuration.front_face,
-                cull_mode: configuration.cull_mode,
-                polygon_mode: configuration.polygon_mode,
-                unclipped_depth: false,
-                conservative: false,
-            },
-            depth_stencil: Some(wgpu::DepthStencilState {
-                format: Renderer::DEPTH_FORMAT,
-                depth_write_enabled: configuration.depth_write_enabled,
-                depth_compare: configuration.depth_compare_function,
-                stencil: wgpu::StencilState::default(),
-                bias: wgpu::DepthBiasState::default(),
-            }),
-            multisample: wgpu::MultisampleState {
-                count: 1,
-                mask: !0,
-                alpha_to_coverage_enabled: false,
-            },
-            fragment: Some(wgpu::FragmentState {
-                module: &shader,
-                entry_point: &configuration.fragment_shader_entrypoint,
-                targets: &[Some(wgpu::ColorTargetState {
-                    format: *surface_format,
-                    blend: configuration.fragment_shader_blend_mode,
-                    write_mask: configuration.fragment_shader_write_mask,
-                })],
-                compilation_options: PipelineCompilationOptions::default(),
-            }),
-            multiview: None,
-        });
+        layouts: &mut BindGroupLayoutCache,
+    ) -> Result<Self, RendererError> {
+        let shader_files = shader_files(&configuration);
+        let compiled = create_render_pipeline(&configuration, device, surface_format, layouts)?;
 
         Ok(Self {
-            pipeline,
+            pipeline: compiled.pipeline,
             configuration,
-            bind_group_layouts,
+            bind_group_layouts: compiled.bind_group_layouts,
             global_bind_groups: Vec::new(),
+            meshes: Vec::new(),
             draw_queue: Vec::new(),
+            shader_files,
+            reflected_layouts: compiled.reflected_layouts,
         })
     }
 
-    pub fn submit_mesh(
+    // Whether a shader file was modified since the pipeline was compiled
+    pub fn shaders_changed(&self) -> bool {
+        self.shader_files
+            .iter()
+            .any(|(path, modified)| modified_time(path) != *modified)
+    }
+
+    // Recompiles the shaders in place. The current render pipeline is kept
+    // when the new shaders fail to load or validate, or declare groups the
+    // existing globals, mesh groups and materials do not match. Buffers
+    // smaller than the new shaders declare would otherwise fail the first
+    // draw rather than the reload.
+    pub fn reload(
         &mut self,
         device: &Device,
-        queue: &Queue,
-        render_target: &TextureView,
-        depth_texture: &TextureView,
-        mesh: MeshHandle,
-        mesh_cache: &SlotMap<Mesh>,
+        surface_format: &TextureFormat,
+        layouts: &mut BindGroupLayoutCache,
+        mesh_cache: &HandleMap<Mesh>,
         material_cache: &MaterialCache,
-    ) {
-        self.draw_queue.push(mesh);
+    ) -> Result<(), RendererError> {
+        // Taken before compiling so a failed reload is not retried until the
+        // files change again
+        self.shader_files = shader_files(&self.configuration);
 
-        // Check the heuristic for submission
-        if self.draw_queue.len() >= 5 {
-            self.submit_pending_draws(
-                device,
-                queue,
-                render_target,
-                depth_texture,
-                mesh_cache,
-                material_cache,
-            );
+        let compiled =
+            create_render_pipeline(&self.configuration, device, surface_format, layouts)?;
+        if let Some(declared_layouts) = &compiled.reflected_layouts {
+            let validate =
+                |group: u32, layout_entries: &[BindGroupLayoutEntry]| match declared_layouts
+                    .get(group as usize)
+                {
+                    Some(declared) => reflection::validate_group(group, declared, layout_entries),
+                    None => Ok(()),
+                };
+            for (group, global) in self.global_bind_groups.iter().enumerate() {
+                validate(group as u32, global.layout_entries())?;
+            }
+            let mesh_group = self.global_group_count();
+            for mesh in self.meshes.iter().filter_map(|mesh| mesh_cache.get(mesh)) {
+                validate(mesh_group, mesh.mesh_uniform_group().layout_entries())?;
+                for material in mesh.materials(material_cache) {
+                    validate(mesh_group + 1, &material.layout_entries())?;
+                }
+            }
         }
+
+        self.pipeline = compiled.pipeline;
+        self.bind_group_layouts = compiled.bind_group_layouts;
+        self.reflected_layouts = compiled.reflected_layouts;
+        Ok(())
     }
 
-    pub fn add_global_bind_group(&mut self, source: &UniformGroupSource, device: &Device) {
-        let uniform_group = UniformGroup::from_source(source, device);
-        self.global_bind_groups.push(uniform_group);
+    // Returns the number of draws now queued
+    pub fn queue_mesh(&mut self, mesh: MeshHandle) -> usize {
+        self.draw_queue.push(mesh);
+        self.draw_queue.len()
+    }
+
+    pub fn queued_draws(&self) -> usize {
+        self.draw_queue.len()
+    }
+
+    // Drops queued draws that no longer satisfy the predicate, used when the
+    // resources they reference are unregistered
+    pub fn retain_queued<F>(&mut self, predicate: F)
+    where
+        F: FnMut(&MeshHandle) -> bool,
+    {
+        self.draw_queue.retain(predicate);
+    }
+
+    pub fn add_mesh(&mut self, mesh: MeshHandle) {
+        self.meshes.push(mesh);
     }
 
-    pub fn flush_queue(
+    pub fn remove_mesh(&mut self, mesh: MeshHandle) {
+        self.meshes.retain(|registered| *registered != mesh);
+    }
+
+    // Globals are bound before the mesh groups, so adding one would move the
+    // groups of the meshes already registered
+    pub fn add_global_bind_group(
         &mut self,
+        source: &UniformGroupSource,
         device: &Device,
-        queue: &Queue,
-        render_target: &TextureView,
-        depth_texture: &TextureView,
-        mesh_cache: &SlotMap<Mesh>,
-        material_cache: &MaterialCache,
-    ) {
-        if self.draw_queue.is_empty() {
-            return;
+        layouts: &mut BindGroupLayoutCache,
+    ) -> Result<(), RendererError> {
+        if !self.meshes.is_empty() {
+            return Err(RendererError::PipelineHasMeshes);
         }
+        let layout_entries = self.uniform_group_layout(self.global_group_count(), source);
+        self.validate_group(self.global_group_count(), &layout_entries)?;
+        let uniform_group = UniformGroup::from_source(source, &layout_entries, device, layouts)?;
+        self.global_bind_groups.push(uniform_group);
+        Ok(())
+    }
 
-        self.submit_pending_draws(
-            device,
-            queue,
-            render_target,
-            depth_texture,
-            mesh_cache,
-            material_cache,
-        );
+    // Global groups are indexed in the order they were added
+    pub fn global_bind_group(&self, index: usize) -> Option<&UniformGroup> {
+        self.global_bind_groups.get(index)
     }
 
-    fn submit_pending_draws(
-        &mut self,
-        device: &Device,
-        queue: &Queue,
-        render_target: &TextureView,
-        depth_texture: &TextureView,
-        mesh_cache: &SlotMap<Mesh>,
-        material_cache: &MaterialCache,
-    ) {
-        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: None });
-        {
-            let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-                label: None,
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: render_target,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: StoreOp::Store,
-                    },
-                })],
-                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                    view: depth_texture,
-                    depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Load,
-                        store: StoreOp::Store,
-                    }),
-                    stencil_ops: None,
-                }),
-                timestamp_writes: None,
-                occlusion_query_set: None,
-            });
-
-            // Set pipeline
-            render_pass.set_pipeline(&self.pipeline);
-
-            // Set global bind groups
-            for (i, global_bind_group) in self.global_bind_groups.iter().enumerate() {
-                render_pass.set_bind_group(i as u32, global_bind_group.bind_group(), &[]);
+    // Mesh uniform groups and materials are bound after the globals
+    pub fn global_group_count(&self) -> u32 {
+        self.global_bind_groups.len() as u32
+    }
+
+    // The sized layout of a uniform group bound to the group. Buffers are
+    // visible to the stages the configured layout gives them, which reflected
+    // configurations derive from the stages that use them.
+    pub fn uniform_group_layout(
+        &self,
+        group: u32,
+        source: &UniformGroupSource,
+    ) -> Vec<BindGroupLayoutEntry> {
+        let mut entries = source.sized_layout_entries();
+        if let Some(configured) = self.configuration.bind_group_layouts.get(group as usize) {
+            for entry in &mut entries {
+                if let Some(declared) = configured
+                    .iter()
+                    .find(|declared| declared.binding == entry.binding)
+                {
+                    entry.visibility = declared.visibility;
+                }
             }
+        }
+        entries
+    }
 
-            // Record commands
-            for mesh_handle in &self.draw_queue {
-                let mesh = mesh_cache.get(mesh_handle).unwrap();
-                mesh.record_commands(
-                    &mut render_pass,
-                    self.global_bind_groups.len() as u32,
-                    material_cache,
-                );
+    // Checks the layout of a uniform group or material, with the size of
+    // each buffer as its minimum binding size, against the group it is bound
+    // to. It has to be identical to the configured layout to be compatible
+    // with the pipeline, and buffers have to be at least as large as the
+    // types a WGSL shader declares for them.
+    pub fn validate_group(
+        &self,
+        group: u32,
+        layout_entries: &[BindGroupLayoutEntry],
+    ) -> Result<(), RendererError> {
+        if let Some(configured) = self.configuration.bind_group_layouts.get(group as usize) {
+            if *configured != reflection::without_sizes(layout_entries) {
+                return Err(RendererError::BindGroupMismatch {
+                    group,
+                    message: String::from("the layout differs from the pipeline configuration"),
+                });
             }
         }
 
-        let command_buffer = encoder.finish();
-        queue.submit([command_buffer]);
+        match self
+            .reflected_layouts
+            .as_ref()
+            .and_then(|layouts| layouts.get(group as usize))
+        {
+            Some(declared) => reflection::validate_group(group, declared, layout_entries),
+            None => Ok(()),
+        }
+    }
+
+    // Records every queued draw into one render pass and returns how many
+    // draws were recorded. No pass is recorded when the queue is empty.
+    pub fn record_queue(
+        &mut self,
+        encoder: &mut CommandEncoder,
+        target: &mut DrawTarget,
+        mesh_cache: &HandleMap<Mesh>,
+        material_cache: &MaterialCache,
+    ) -> Result<usize, RendererError> {
+        if self.draw_queue.is_empty() {
+            return Ok(0);
+        }
+
+        // The queue is emptied even when recording fails so a bad mesh does
+        // not poison every following frame
+        let draw_queue = std::mem::take(&mut self.draw_queue);
+        let mut render_pass = target.begin_render_pass(encoder, "pipeline");
+
+        // Set pipeline
+        render_pass.set_pipeline(&self.pipeline);
+
+        // Set global bind groups
+        for (i, global_bind_group) in self.global_bind_groups.iter().enumerate() {
+            render_pass.set_bind_group(i as u32, global_bind_group.bind_group(), &[]);
+        }
+
+        // Record commands
+        for mesh_handle in &draw_queue {
+            let mesh = mesh_cache
+                .get(mesh_handle)
+                .ok_or(RendererError::InvalidHandle)?;
+            mesh.record_commands(
+                &mut render_pass,
+                self.global_bind_groups.len() as u32,
+                material_cache,
+            )?;
+        }
+        Ok(draw_queue.len())
     }
 }
+
+// Creates the pipeline inside a validation error scope so that mistakes only
+// the device catches, such as a missing entry point or bind groups that do not
+// match the shader, are returned instead of reaching the device's error
+// handler, which panics by default. The bind group layouts are created before
+// the scope, so that a layout the device rejects is not cached.
+fn create_render_pipeline(
+    configuration: &PipelineConfiguration,
+    device: &Device,
+    surface_format: &TextureFormat,
+    layouts: &mut BindGroupLayoutCache,
+) -> Result<CompiledPipeline, RendererError> {
+    let label = format!("pipeline({})", configuration.shader_source.label());
+    let bind_group_layouts = configuration
+        .bind_group_layouts
+        .iter()
+        .map(|entries| layouts.try_get_or_create(device, entries))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|error| RendererError::PipelineCreation {
+            label: label.clone(),
+            message: error.to_string(),
+        })?;
+
+    device.push_error_scope(ErrorFilter::Validation);
+    let created = build_render_pipeline(
+        configuration,
+        device,
+        surface_format,
+        bind_group_layouts,
+        &label,
+    );
+    let error = pollster::block_on(device.pop_error_scope());
+    match error {
+        Some(error) => Err(RendererError::PipelineCreation {
+            label,
+            message: error.to_string(),
+        }),
+        None => created,
+    }
+}
+
+fn build_render_pipeline(
+    configuration: &PipelineConfiguration,
+    device: &Device,
+    surface_format: &TextureFormat,
+    bind_group_layouts: Vec<Arc<BindGroupLayout>>,
+    label: &str,
+) -> Result<CompiledPipeline, RendererError> {
+    let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+        label: Some(&format!("{}/render_pipeline_layout", label)),
+        bind_group_layouts: &bind_group_layouts
+            .iter()
+            .map(|layout| layout.as_ref())
+            .collect::<Vec<_>>(),
+        push_constant_ranges: &[],
+    });
+
+    // Without a separate fragment shader source both stages come from
+    // the same module
+    let (vertex_name, vertex_stages) = match configuration.fragment_shader_source {
+        Some(_) => ("vertex_shader", &[ShaderStage::Vertex][..]),
+        None => ("shader", &[ShaderStage::Vertex, ShaderStage::Fragment][..]),
+    };
+    let (vertex_shader, vertex_module) = configuration.shader_source.create_module(
+        device,
+        &format!("{}/{}", label, vertex_name),
+        vertex_stages,
+    )?;
+    let (fragment_shader, fragment_module) = match &configuration.fragment_shader_source {
+        Some(source) => {
+            let (shader, module) = source.create_module(
+                device,
+                &format!("{}/fragment_shader", label),
+                &[ShaderStage::Fragment],
+            )?;
+            (Some(shader), module)
+        }
+        None => (None, None),
+    };
+
+    let vertex_buffer_layouts: Vec<VertexBufferLayout> = configuration
+        .vertex_buffer_layouts
+        .iter()
+        .map(VertexBufferLayoutSource::layout)
+        .collect();
+    let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some(label),
+        layout: Some(&pipeline_layout),
+        vertex: VertexState {
+            module: &vertex_shader,
+            entry_point: &configuration.vertex_shader_entrypoint,
+            buffers: &vertex_buffer_layouts,
+            compilation_options: PipelineCompilationOptions::default(),
+        },
+        primitive: wgpu::PrimitiveState {
+            topology: configuration.topology,
+            strip_index_format: configuration.strip_index_format,
+            front_face: configuration.front_face,
+            cull_mode: configuration.cull_mode,
+            polygon_mode: configuration.polygon_mode,
+            unclipped_depth: false,
+            conservative: false,
+        },
+        depth_stencil: Some(wgpu::DepthStencilState {
+            format: Renderer::DEPTH_FORMAT,
+            depth_write_enabled: configuration.depth_write_enabled,
+            depth_compare: configuration.depth_compare_function,
+            stencil: wgpu::StencilState::default(),
+            bias: wgpu::DepthBiasState::default(),
+        }),
+        multisample: wgpu::MultisampleState {
+            count: 1,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        fragment: Some(wgpu::FragmentState {
+            module: fragment_shader.as_ref().unwrap_or(&vertex_shader),
+            entry_point: &configuration.fragment_shader_entrypoint,
+            targets: &[Some(wgpu::ColorTargetState {
+                format: *surface_format,
+                blend: configuration.fragment_shader_blend_mode,
+                write_mask: configuration.fragment_shader_write_mask,
+            })],
+            compilation_options: PipelineCompilationOptions::default(),
+        }),
+        multiview: None,
+    });
+
+    // A shader the reflection does not understand is simply not validated
+    let modules: Vec<&Module> = vertex_module.iter().chain(&fragment_module).collect();
+    let reflected_layouts = match modules.is_empty() {
+        true => None,
+        false => match reflection::bind_group_layouts(&modules) {
+            Ok(layouts) => Some(layouts),
+            Err(error) => {
+                log::warn!(
+                    "Bind groups of {} are not validated against its shaders: {}",
+                    label,
+                    error
+                );
+                None
+            }
+        },
+    };
+
+    Ok(CompiledPipeline {
+        pipeline,
+        bind_group_layouts,
+        reflected_layouts,
+    })
+}
+
+fn shader_files(configuration: &PipelineConfiguration) -> Vec<(PathBuf, Option<SystemTime>)> {
+    std::iter::once(&configuration.shader_source)
+        .chain(&configuration.fragment_shader_source)
+        .filter_map(ShaderSource::path)
+        .map(|path| (path.to_path_buf(), modified_time(path)))
+        .collect()
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
@@ -1,10 +1,22 @@
 // Creates a codependency on WGPU
 
+use crate::{
+    error::{RendererError, ShaderError},
+    reflection, shader_library,
+    shader_source::ShaderSource,
+};
+use std::borrow::Cow;
+use wgpu::{
+    naga::ShaderStage, BindGroupLayoutEntry, BindingType, BufferBindingType, SamplerBindingType,
+    ShaderStages, TextureSampleType, TextureViewDimension, VertexAttribute, VertexBufferLayout,
+    VertexStepMode,
+};
+
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct PipelineConfiguration {
-    pub shader_path: String,
+    pub shader_source: ShaderSource,
     pub vertex_shader_entrypoint: String,
-    pub vertex_buffer_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
+    pub vertex_buffer_layouts: Vec<VertexBufferLayoutSource>,
     pub topology: wgpu::PrimitiveTopology,
     pub strip_index_format: Option<wgpu::IndexFormat>,
     pub front_face: wgpu::FrontFace,
@@ -12,8 +24,212 @@ pub struct PipelineConfiguration {
     pub polygon_mode: wgpu::PolygonMode,
     pub depth_write_enabled: bool,
     pub depth_compare_function: wgpu::CompareFunction,
+    // None takes the fragment shader from shader_source
+    pub fragment_shader_source: Option<ShaderSource>,
     pub fragment_shader_entrypoint: String,
     pub fragment_shader_blend_mode: Option<wgpu::BlendState>,
     pub fragment_shader_write_mask: wgpu::ColorWrites,
     pub bind_group_layouts: Vec<Vec<wgpu::BindGroupLayoutEntry>>,
 }
+
+// Owned form of wgpu::VertexBufferLayout, so that configurations built at
+// runtime such as reflected ones can hold their attributes. The wgpu layout
+// is built from it when the pipeline is created.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub struct VertexBufferLayoutSource {
+    pub array_stride: wgpu::BufferAddress,
+    pub step_mode: VertexStepMode,
+    pub attributes: Vec<VertexAttribute>,
+}
+
+impl VertexBufferLayoutSource {
+    pub fn layout(&self) -> VertexBufferLayout<'_> {
+        VertexBufferLayout {
+            array_stride: self.array_stride,
+            step_mode: self.step_mode,
+            attributes: &self.attributes,
+        }
+    }
+}
+
+const POSITION_ATTRIBUTES: [VertexAttribute; 1] = wgpu::vertex_attr_array![0 => Float32x3];
+const POSITION_UV_ATTRIBUTES: [VertexAttribute; 2] =
+    wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x2];
+const POSITION_COLOR_ATTRIBUTES: [VertexAttribute; 2] =
+    wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x4];
+const POSITION_NORMAL_UV_ATTRIBUTES: [VertexAttribute; 3] =
+    wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2];
+
+// Built-in pipelines share one layout so the same uniforms can be used with
+// any of them:
+//   group 0 (pipeline globals): binding 0 is the camera
+//     { view_projection: mat4, position: vec4 }, the lit pipelines add the
+//     light { direction: vec4, color: vec4, ambient: vec4 } at binding 1
+//   group 1 (mesh uniform group): binding 0 is the mesh
+//     { model: mat4, color: vec4 }, the lit pipelines add
+//     specular: vec4 with the shininess in w
+//   group 2 (material): the textured and lit pipelines sample the first
+//     texture of the material
+// Matrices are column major, as produced by glam's to_cols_array.
+impl PipelineConfiguration {
+    // Vertices are a position (vec3)
+    pub fn unlit_color() -> Self {
+        Self::builtin(
+            shader_library::UNLIT_COLOR,
+            &POSITION_ATTRIBUTES,
+            vec![uniform_group(1), uniform_group(1)],
+        )
+    }
+
+    // Vertices are a position (vec3) and a texture coordinate (vec2)
+    pub fn unlit_textured() -> Self {
+        Self::builtin(
+            shader_library::UNLIT_TEXTURED,
+            &POSITION_UV_ATTRIBUTES,
+            vec![uniform_group(1), uniform_group(1), texture_group()],
+        )
+    }
+
+    // Vertices are a position (vec3) and a linear color (vec4)
+    pub fn vertex_color() -> Self {
+        Self::builtin(
+            shader_library::VERTEX_COLOR,
+            &POSITION_COLOR_ATTRIBUTES,
+            vec![uniform_group(1), uniform_group(1)],
+        )
+    }
+
+    // Vertices are a position (vec3), a normal (vec3) and a texture
+    // coordinate (vec2)
+    pub fn lambert() -> Self {
+        Self {
+            fragment_shader_entrypoint: String::from("fs_lambert"),
+            ..Self::blinn_phong()
+        }
+    }
+
+    // Same vertices as lambert
+    pub fn blinn_phong() -> Self {
+        Self {
+            fragment_shader_entrypoint: String::from("fs_blinn_phong"),
+            ..Self::builtin(
+                shader_library::LIT,
+                &POSITION_NORMAL_UV_ATTRIBUTES,
+                vec![uniform_group(2), uniform_group(1), texture_group()],
+            )
+        }
+    }
+
+    // Derives the entry points and the vertex buffer and bind group layouts
+    // from a WGSL shader, taking the first vertex and fragment entry points
+    // and one vertex buffer with the vertex inputs packed in location order.
+    // The remaining fields take the same defaults as the built-in pipelines.
+    pub fn reflect(shader_source: ShaderSource) -> Result<Self, RendererError> {
+        let label = shader_source.label();
+        let error =
+            |message: String| RendererError::ShaderReflection(ShaderError::new(&label, message));
+        let (_, module) = shader_source
+            .parse_wgsl(&label)?
+            .ok_or_else(|| error(String::from("only WGSL shaders can be reflected")))?;
+
+        let vertex_entrypoint = reflection::entry_point(&module, ShaderStage::Vertex)
+            .ok_or_else(|| error(String::from("no vertex entry point")))?;
+        let fragment_entrypoint = reflection::entry_point(&module, ShaderStage::Fragment)
+            .ok_or_else(|| error(String::from("no fragment entry point")))?;
+        let attributes =
+            reflection::vertex_attributes(&module, vertex_entrypoint).map_err(error)?;
+
+        let bind_group_layouts = reflection::bind_group_layouts(&[&module])
+            .map_err(error)?
+            .iter()
+            .map(|entries| reflection::without_sizes(entries))
+            .collect();
+
+        Ok(Self {
+            vertex_shader_entrypoint: vertex_entrypoint.to_string(),
+            fragment_shader_entrypoint: fragment_entrypoint.to_string(),
+            ..Self::with_defaults(shader_source, attributes, bind_group_layouts)
+        })
+    }
+
+    fn builtin(
+        shader: &'static str,
+        attributes: &'static [VertexAttribute],
+        bind_group_layouts: Vec<Vec<BindGroupLayoutEntry>>,
+    ) -> Self {
+        Self::with_defaults(
+            ShaderSource::Wgsl(Cow::Borrowed(shader)),
+            attributes.to_vec(),
+            bind_group_layouts,
+        )
+    }
+
+    fn with_defaults(
+        shader_source: ShaderSource,
+        attributes: Vec<VertexAttribute>,
+        bind_group_layouts: Vec<Vec<BindGroupLayoutEntry>>,
+    ) -> Self {
+        Self {
+            shader_source,
+            vertex_shader_entrypoint: String::from("vs_main"),
+            vertex_buffer_layouts: vec![VertexBufferLayoutSource {
+                array_stride: attributes
+                    .iter()
+                    .map(|attribute| attribute.format.size())
+                    .sum(),
+                step_mode: VertexStepMode::Vertex,
+                attributes,
+            }],
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            polygon_mode: wgpu::PolygonMode::Fill,
+            depth_write_enabled: true,
+            depth_compare_function: wgpu::CompareFunction::Less,
+            fragment_shader_source: None,
+            fragment_shader_entrypoint: String::from("fs_main"),
+            fragment_shader_blend_mode: Some(wgpu::BlendState::ALPHA_BLENDING),
+            fragment_shader_write_mask: wgpu::ColorWrites::ALL,
+            bind_group_layouts,
+        }
+    }
+}
+
+// Layout of a uniform group with the given number of uniforms
+fn uniform_group(uniforms: u32) -> Vec<BindGroupLayoutEntry> {
+    (0..uniforms)
+        .map(|binding| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::VERTEX_FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        })
+        .collect()
+}
+
+// Layout of a material with a single texture
+fn texture_group() -> Vec<BindGroupLayoutEntry> {
+    vec![
+        BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::VERTEX_FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        },
+        BindGroupLayoutEntry {
+            binding: 1,
+            visibility: ShaderStages::VERTEX_FRAGMENT,
+            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        },
+    ]
+}
@@ -0,0 +1,305 @@
+use crate::{error::RendererError, uniform_group::buffer_visibility};
+use std::num::NonZeroU64;
+use wgpu::{
+    naga::{
+        valid::{Capabilities, ValidationFlags, Validator},
+        AddressSpace, Binding, ImageClass, ImageDimension, Module, ScalarKind, ShaderStage,
+        StorageAccess, TypeInner, VectorSize,
+    },
+    BindGroupLayoutEntry, BindingType, BufferBindingType, SamplerBindingType, ShaderStages,
+    TextureSampleType, TextureViewDimension, VertexAttribute, VertexFormat,
+};
+
+// Reflects the bind group layouts declared by the modules. Buffers carry the
+// size of the type the shader declares as their minimum binding size and are
+// visible to the stages whose entry points use them, since downlevel adapters
+// may not support storage buffers in the vertex stage. Textures and samplers
+// are visible to both stages like in the layouts of materials, which are
+// shared between pipelines. Groups the modules skip are left empty.
+pub fn bind_group_layouts(modules: &[&Module]) -> Result<Vec<Vec<BindGroupLayoutEntry>>, String> {
+    let mut groups: Vec<Vec<BindGroupLayoutEntry>> = Vec::new();
+    for module in modules {
+        let info = Validator::new(ValidationFlags::all(), Capabilities::all())
+            .validate(module)
+            .map_err(|error| error.to_string())?;
+        for (handle, variable) in module.global_variables.iter() {
+            let Some(binding) = &variable.binding else {
+                continue;
+            };
+            let inner = &module.types[variable.ty].inner;
+            let ty = match (variable.space, inner) {
+                (AddressSpace::Uniform, _) => BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(inner.size(module.to_ctx()) as u64),
+                },
+                (AddressSpace::Storage { access }, _) => BindingType::Buffer {
+                    ty: BufferBindingType::Storage {
+                        read_only: !access.contains(StorageAccess::STORE),
+                    },
+                    has_dynamic_offset: false,
+                    min_binding_size: NonZeroU64::new(inner.size(module.to_ctx()) as u64),
+                },
+                (AddressSpace::Handle, TypeInner::Sampler { comparison }) => {
+                    BindingType::Sampler(match comparison {
+                        true => SamplerBindingType::Comparison,
+                        false => SamplerBindingType::Filtering,
+                    })
+                }
+                (
+                    AddressSpace::Handle,
+                    TypeInner::Image {
+                        dim,
+                        arrayed,
+                        class,
+                    },
+                ) => {
+                    let view_dimension = match (dim, arrayed) {
+                        (ImageDimension::D1, _) => TextureViewDimension::D1,
+                        (ImageDimension::D2, false) => TextureViewDimension::D2,
+                        (ImageDimension::D2, true) => TextureViewDimension::D2Array,
+                        (ImageDimension::D3, _) => TextureViewDimension::D3,
+                        (ImageDimension::Cube, false) => TextureViewDimension::Cube,
+                        (ImageDimension::Cube, true) => TextureViewDimension::CubeArray,
+                    };
+                    let (sample_type, multisampled) = match class {
+                        ImageClass::Sampled { kind, multi } => {
+                            let sample_type = match kind {
+                                ScalarKind::Sint => TextureSampleType::Sint,
+                                ScalarKind::Uint => TextureSampleType::Uint,
+                                _ => TextureSampleType::Float { filterable: true },
+                            };
+                            (sample_type, *multi)
+                        }
+                        ImageClass::Depth { multi } => (TextureSampleType::Depth, *multi),
+                        ImageClass::Storage { .. } => {
+                            return Err(format!(
+                                "group {} binding {}: storage textures can not be reflected",
+                                binding.group, binding.binding
+                            ))
+                        }
+                    };
+                    BindingType::Texture {
+                        sample_type,
+                        view_dimension,
+                        multisampled,
+                    }
+                }
+                _ => {
+                    return Err(format!(
+                        "group {} binding {}: unsupported resource type",
+                        binding.group, binding.binding
+                    ))
+                }
+            };
+
+            let visibility = match ty {
+                BindingType::Buffer { .. } => module
+                    .entry_points
+                    .iter()
+                    .enumerate()
+                    .filter(|(index, _)| !info.get_entry_point(*index)[handle].is_empty())
+                    .fold(ShaderStages::NONE, |stages, (_, entry_point)| {
+                        stages | shader_stages(entry_point.stage)
+                    }),
+                _ => ShaderStages::VERTEX_FRAGMENT,
+            };
+
+            let group = binding.group as usize;
+            if groups.len() <= group {
+                groups.resize_with(group + 1, Vec::new);
+            }
+            // A separate fragment module may declare the same binding again
+            match groups[group]
+                .iter_mut()
+                .find(|entry| entry.binding == binding.binding)
+            {
+                Some(entry) => entry.visibility |= visibility,
+                None => groups[group].push(BindGroupLayoutEntry {
+                    binding: binding.binding,
+                    visibility,
+                    ty,
+                    count: None,
+                }),
+            }
+        }
+    }
+
+    for group in &mut groups {
+        group.sort_by_key(|entry| entry.binding);
+        // Buffers no entry point uses are visible like in uniform groups
+        for entry in group.iter_mut() {
+            if let BindingType::Buffer { ty, .. } = entry.ty {
+                if entry.visibility.is_empty() {
+                    entry.visibility = buffer_visibility(ty);
+                }
+            }
+        }
+    }
+    Ok(groups)
+}
+
+fn shader_stages(stage: ShaderStage) -> ShaderStages {
+    match stage {
+        ShaderStage::Vertex => ShaderStages::VERTEX,
+        ShaderStage::Fragment => ShaderStages::FRAGMENT,
+        ShaderStage::Compute => ShaderStages::COMPUTE,
+    }
+}
+
+// Name of the first entry point of the stage
+pub fn entry_point(module: &Module, stage: ShaderStage) -> Option<&str> {
+    module
+        .entry_points
+        .iter()
+        .find(|entry_point| entry_point.stage == stage)
+        .map(|entry_point| entry_point.name.as_str())
+}
+
+// Reflects the inputs of a vertex entry point as the attributes of a single
+// tightly packed vertex buffer, ordered by location
+pub fn vertex_attributes(
+    module: &Module,
+    entry_point: &str,
+) -> Result<Vec<VertexAttribute>, String> {
+    let function = &module
+        .entry_points
+        .iter()
+        .find(|candidate| candidate.stage == ShaderStage::Vertex && candidate.name == entry_point)
+        .ok_or_else(|| format!("no vertex entry point named {}", entry_point))?
+        .function;
+
+    // Inputs are either arguments or members of argument structures
+    let mut inputs = Vec::new();
+    for argument in &function.arguments {
+        match (&argument.binding, &module.types[argument.ty].inner) {
+            (Some(binding), inner) => inputs.push((binding, inner)),
+            (None, TypeInner::Struct { members, .. }) => {
+                for member in members {
+                    if let Some(binding) = &member.binding {
+                        inputs.push((binding, &module.types[member.ty].inner));
+                    }
+                }
+            }
+            (None, _) => {}
+        }
+    }
+
+    let mut locations = Vec::new();
+    for (binding, inner) in inputs {
+        if let Binding::Location { location, .. } = binding {
+            let format = vertex_format(inner)
+                .ok_or_else(|| format!("location {}: unsupported vertex input type", location))?;
+            locations.push((*location, format));
+        }
+    }
+    locations.sort_by_key(|(location, _)| *location);
+
+    let mut offset = 0;
+    Ok(locations
+        .into_iter()
+        .map(|(shader_location, format)| {
+            let attribute = VertexAttribute {
+                format,
+                offset,
+                shader_location,
+            };
+            offset += format.size();
+            attribute
+        })
+        .collect())
+}
+
+fn vertex_format(inner: &TypeInner) -> Option<VertexFormat> {
+    let (size, scalar) = match inner {
+        TypeInner::Scalar(scalar) => (None, scalar),
+        TypeInner::Vector { size, scalar } => (Some(size), scalar),
+        _ => return None,
+    };
+    if scalar.width != 4 {
+        return None;
+    }
+
+    use VertexFormat::*;
+    let formats = match scalar.kind {
+        ScalarKind::Float => [Float32, Float32x2, Float32x3, Float32x4],
+        ScalarKind::Sint => [Sint32, Sint32x2, Sint32x3, Sint32x4],
+        ScalarKind::Uint => [Uint32, Uint32x2, Uint32x3, Uint32x4],
+        _ => return None,
+    };
+    Some(match size {
+        None => formats[0],
+        Some(VectorSize::Bi) => formats[1],
+        Some(VectorSize::Tri) => formats[2],
+        Some(VectorSize::Quad) => formats[3],
+    })
+}
+
+// Checks the layout a uniform group or material creates for itself against
+// the layout the shader declares for the group it is bound to. Buffers may
+// be larger than the shader needs.
+pub fn validate_group(
+    group: u32,
+    expected: &[BindGroupLayoutEntry],
+    actual: &[BindGroupLayoutEntry],
+) -> Result<(), RendererError> {
+    let mismatch = |message: String| RendererError::BindGroupMismatch { group, message };
+
+    if expected.len() != actual.len() {
+        return Err(mismatch(format!(
+            "the shader declares {} bindings but {} are provided",
+            expected.len(),
+            actual.len()
+        )));
+    }
+
+    for entry in actual {
+        let declared = expected
+            .iter()
+            .find(|declared| declared.binding == entry.binding)
+            .ok_or_else(|| {
+                mismatch(format!(
+                    "binding {} is not declared by the shader",
+                    entry.binding
+                ))
+            })?;
+
+        let compatible = match (declared.ty, entry.ty) {
+            (
+                BindingType::Buffer {
+                    ty: declared_ty,
+                    min_binding_size: declared_size,
+                    ..
+                },
+                BindingType::Buffer {
+                    ty: actual_ty,
+                    min_binding_size: actual_size,
+                    ..
+                },
+            ) => declared_ty == actual_ty && actual_size >= declared_size,
+            (declared_ty, actual_ty) => declared_ty == actual_ty,
+        };
+        if !compatible {
+            return Err(mismatch(format!(
+                "binding {} is {:?} but the shader declares {:?}",
+                entry.binding, entry.ty, declared.ty
+            )));
+        }
+    }
+    Ok(())
+}
+
+// Uniform groups and materials create their layouts without minimum binding
+// sizes, so layouts compared with theirs must not have them either
+pub fn without_sizes(entries: &[BindGroupLayoutEntry]) -> Vec<BindGroupLayoutEntry> {
+    let mut entries = entries.to_vec();
+    for entry in &mut entries {
+        if let BindingType::Buffer {
+            min_binding_size, ..
+        } = &mut entry.ty
+        {
+            *min_binding_size = None;
+        }
+    }
+    entries
+}
@@ -0,0 +1,243 @@
+use crate::{error::RendererError, texture::Texture};
+use wgpu::{
+    Device, PresentMode, Surface, SurfaceConfiguration, SurfaceError, SurfaceTexture,
+    TextureFormat, TextureView,
+};
+
+// The color target that pipelines draw into. A windowed renderer draws into
+// the swapchain images of its surface while a headless renderer owns a single
+// offscreen texture that is reused every frame.
+pub enum RenderTarget<'a> {
+    Surface {
+        surface: Surface<'a>,
+        configuration: SurfaceConfiguration,
+        present_modes: Vec<PresentMode>,
+        texture: Option<SurfaceTexture>,
+        view: Option<TextureView>,
+        // Set when the configuration changed while a texture was held, the
+        // surface is reconfigured once the texture is released
+        reconfigure: bool,
+    },
+    Offscreen {
+        color_texture: Texture,
+        format: TextureFormat,
+    },
+}
+
+impl<'a> RenderTarget<'a> {
+    pub fn format(&self) -> TextureFormat {
+        match self {
+            RenderTarget::Surface { configuration, .. } => configuration.format,
+            RenderTarget::Offscreen { format, .. } => *format,
+        }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            RenderTarget::Surface { configuration, .. } => {
+                (configuration.width, configuration.height)
+            }
+            RenderTarget::Offscreen { color_texture, .. } => (
+                color_texture.texture().width(),
+                color_texture.texture().height(),
+            ),
+        }
+    }
+
+    // Whether a surface texture of the current configuration is held
+    pub fn holds_texture(&self) -> bool {
+        matches!(
+            self,
+            RenderTarget::Surface {
+                texture: Some(_),
+                ..
+            }
+        )
+    }
+
+    // Whether a texture is held that draws can be recorded into
+    pub fn is_acquired(&self) -> bool {
+        match self {
+            RenderTarget::Surface { texture, .. } => texture.is_some(),
+            RenderTarget::Offscreen { .. } => true,
+        }
+    }
+
+    pub fn view(&self) -> &TextureView {
+        match self {
+            RenderTarget::Surface { view, .. } => view.as_ref().unwrap(),
+            RenderTarget::Offscreen { color_texture, .. } => color_texture.view(),
+        }
+    }
+
+    pub fn texture(&self) -> &wgpu::Texture {
+        match self {
+            RenderTarget::Surface { texture, .. } => &texture.as_ref().unwrap().texture,
+            RenderTarget::Offscreen { color_texture, .. } => color_texture.texture(),
+        }
+    }
+
+    // Acquires the next surface texture unless one is already held. Returns
+    // false when no texture could be acquired and the frame must be skipped.
+    // Offscreen targets are always available.
+    pub fn acquire(&mut self, device: &Device) -> Result<bool, RendererError> {
+        if let RenderTarget::Surface {
+            surface,
+            configuration,
+            texture,
+            view,
+            ..
+        } = self
+        {
+            if texture.is_some() {
+                return Ok(true);
+            }
+
+            let next_texture = match surface.get_current_texture() {
+                Ok(next_texture) => next_texture,
+                Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                    // The swapchain no longer matches the window (display
+                    // change, GPU reset, resume from sleep), rebuild it and retry
+                    surface.configure(device, configuration);
+                    match surface.get_current_texture() {
+                        Ok(next_texture) => next_texture,
+                        Err(SurfaceError::Outdated | SurfaceError::Timeout) => return Ok(false),
+                        Err(error) => return Err(error.into()),
+                    }
+                }
+                Err(SurfaceError::Timeout) => return Ok(false),
+                Err(error) => return Err(error.into()),
+            };
+
+            *view = Some(create_surface_view(&next_texture, configuration.format));
+            *texture = Some(next_texture);
+        }
+        Ok(true)
+    }
+
+    // Presents the current surface texture. The next texture is acquired
+    // lazily when the next frame starts drawing. Offscreen targets have nothing
+    // to present and keep their texture.
+    pub fn present(&mut self, device: &Device) {
+        if let RenderTarget::Surface {
+            surface,
+            configuration,
+            texture,
+            view,
+            reconfigure,
+            ..
+        } = self
+        {
+            view.take();
+            if let Some(texture) = texture.take() {
+                // Still presentable but the swapchain should be rebuilt
+                *reconfigure |= texture.suboptimal;
+                texture.present();
+            }
+            if std::mem::take(reconfigure) {
+                surface.configure(device, configuration);
+            }
+        }
+    }
+
+    // Releases the current surface texture without presenting it, for frames
+    // that are skipped after it was acquired
+    pub fn discard(&mut self, device: &Device) {
+        if let RenderTarget::Surface {
+            surface,
+            configuration,
+            texture,
+            view,
+            reconfigure,
+            ..
+        } = self
+        {
+            view.take();
+            texture.take();
+            if std::mem::take(reconfigure) {
+                surface.configure(device, configuration);
+            }
+        }
+    }
+
+    pub fn present_mode(&self) -> Option<PresentMode> {
+        match self {
+            RenderTarget::Surface { configuration, .. } => Some(configuration.present_mode),
+            RenderTarget::Offscreen { .. } => None,
+        }
+    }
+
+    // The surface is only reconfigured once the texture of the frame in
+    // progress is presented, the texture belongs to the old swapchain
+    pub fn set_present_mode(
+        &mut self,
+        device: &Device,
+        present_mode: PresentMode,
+    ) -> Result<(), RendererError> {
+        if let RenderTarget::Surface {
+            surface,
+            configuration,
+            present_modes,
+            texture,
+            reconfigure,
+            ..
+        } = self
+        {
+            if !supports_present_mode(present_modes, present_mode) {
+                return Err(RendererError::UnsupportedPresentMode(present_mode));
+            }
+
+            configuration.present_mode = present_mode;
+            match texture {
+                Some(_) => *reconfigure = true,
+                None => surface.configure(device, configuration),
+            }
+        }
+        Ok(())
+    }
+
+    // Reallocates the target for the new size. The caller must not resize to
+    // a zero sized target, which wgpu rejects, or while a surface texture is
+    // held.
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        match self {
+            RenderTarget::Surface {
+                surface,
+                configuration,
+                ..
+            } => {
+                configuration.width = width;
+                configuration.height = height;
+                surface.configure(device, configuration);
+            }
+            RenderTarget::Offscreen {
+                color_texture,
+                format,
+            } => {
+                *color_texture =
+                    Texture::new_render_target(device, width, height, "color_texture", format);
+            }
+        }
+    }
+}
+
+// The automatic modes fall back to a supported mode so they are always valid
+pub fn supports_present_mode(present_modes: &[PresentMode], present_mode: PresentMode) -> bool {
+    matches!(
+        present_mode,
+        PresentMode::AutoVsync | PresentMode::AutoNoVsync
+    ) || present_modes.contains(&present_mode)
+}
+
+pub fn create_surface_view(texture: &SurfaceTexture, format: TextureFormat) -> TextureView {
+    texture.texture.create_view(&wgpu::TextureViewDescriptor {
+        label: None,
+        format: Some(format),
+        dimension: None,
+        aspect: wgpu::TextureAspect::All,
+        base_mip_level: 0,
+        mip_level_count: None,
+        base_array_layer: 0,
+        array_layer_count: None,
+    })
+}
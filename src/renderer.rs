@@ -1,76 +1,96 @@
 use crate::{
+    bind_group_layout_cache::BindGroupLayoutCache,
+    debug_draw::DebugDraw,
+    error::RendererError,
+    frame::{DrawTarget, Frame, Viewport},
+    frame_capture,
+    frame_encoder::{FrameEncoder, FrameStatistics},
+    handle::{HandleMap, MaterialHandle, MeshHandle, PipelineHandle},
     material::{Material, MaterialSource},
     material_cache::MaterialCache,
-    mesh::{Mesh, MeshHandle, MeshSource},
+    mesh::{Mesh, MeshSource},
     pipeline::Pipeline,
     pipeline_configuration::PipelineConfiguration,
+    render_target::{self, RenderTarget},
     renderer_configuration::RendererConfiguration,
+    retirement_queue::{RetiredResource, RetirementQueue},
+    submission_policy::SubmissionPolicy,
     texture::Texture,
+    typed_uniform::{TypedUniform, WgslType},
     uniform_group::UniformGroupSource,
 };
+use glam::Vec2;
+use image::RgbaImage;
 use raw_window_handle::{HasDisplayHandle, HasWindowHandle};
-use slot_map::{SlotMap, SlotMapIndex};
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    time::{Duration, Instant},
+};
 use wgpu::{
-    Color, CommandEncoderDescriptor, Device, DeviceDescriptor, Dx12Compiler, Features,
-    Gles3MinorVersion, Instance, InstanceDescriptor, Limits, PowerPreference, PresentMode, Queue,
-    RenderPassDescriptor, RequestAdapterOptions, StoreOp, Surface, SurfaceConfiguration,
-    SurfaceTexture, TextureFormat, TextureView,
+    Adapter, AdapterInfo, Color, CompositeAlphaMode, Device, DeviceDescriptor, Gles3MinorVersion,
+    Instance, InstanceDescriptor, PresentMode, Queue, RequestAdapterOptions, Surface,
+    TextureFormat,
 };
 
 pub struct Renderer<'a> {
-    surface: Surface<'a>,
-    surface_configuration: SurfaceConfiguration,
+    target: RenderTarget<'a>,
     device: Device,
     queue: Queue,
     depth_texture: Texture,
-    pipelines: SlotMap<Pipeline>,
-    pipeline_lookup: HashMap<PipelineConfiguration, SlotMapIndex>,
+    pipelines: HandleMap<Pipeline>,
+    pipeline_lookup: HashMap<PipelineConfiguration, PipelineHandle>,
+    bind_group_layouts: BindGroupLayoutCache,
 
-    mesh_cache: SlotMap<Mesh>, // The meshes/sub_meshes need to be accessed when the mesh handle is returned
+    mesh_cache: HandleMap<Mesh>, // The meshes/sub_meshes need to be accessed when the mesh handle is returned
     material_cache: MaterialCache,
+    retirement_queue: RetirementQueue,
+    frame_encoder: FrameEncoder,
+    frame_number: u64,
+    last_frame_statistics: FrameStatistics,
+    // The viewport of the current frame that queued draws are recorded with
+    viewport: Option<Viewport>,
+
+    // The target is cleared lazily by the first draw of each frame
+    clear_pending: bool,
+    clear_color: Option<Color>,
+    clear_depth: Option<f32>,
+
+    // Set while the window is minimized and has no area to render into
+    paused: bool,
+    // A resize requested while the surface texture of a frame is held, which
+    // is applied once the frame is presented
+    pending_size: Option<(u32, u32)>,
+
+    shader_reload_interval: Option<Duration>,
+    last_shader_reload: Instant,
+}
 
-    surface_texture: Option<SurfaceTexture>,
-    surface_view: Option<TextureView>,
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrameStatus {
+    // The frame was drawn and presented (or written to the offscreen target)
+    Presented,
+    // Nothing was drawn, either because rendering is paused or because the
+    // surface had no texture available
+    Skipped,
 }
 
 impl<'a> Renderer<'a> {
     pub const DEPTH_FORMAT: TextureFormat = TextureFormat::Depth32Float;
+    pub const OFFSCREEN_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
 
-    pub fn new<W>(window: &'a W, configuration: &RendererConfiguration) -> Result<Self, String>
+    pub fn new<W>(
+        window: &'a W,
+        configuration: &RendererConfiguration,
+    ) -> Result<Self, RendererError>
     where
         W: HasWindowHandle + HasDisplayHandle + Send + Sync,
     {
-        let instance_descriptor = InstanceDescriptor {
-            backends: /*wgpu::Backends::DX11
-                | wgpu::Backends::DX12
-                | wgpu::Backends::GL */
-                /*|*/ wgpu::Backends::VULKAN,
-                //| wgpu::Backends::METAL,
-            flags: configuration.instance_flags,
-            dx12_shader_compiler: Dx12Compiler::Fxc,
-            gles_minor_version: Gles3MinorVersion::Automatic,
-        };
-        let instance = Instance::new(instance_descriptor);
-        let surface = instance.create_surface(window).unwrap();
-        let adapter = instance.request_adapter(&RequestAdapterOptions {
-            power_preference: PowerPreference::HighPerformance,
-            force_fallback_adapter: false,
-            compatible_surface: Some(&surface),
-        });
-        let adapter = pollster::block_on(adapter)
-            .ok_or(String::from("Failed to acquire graphics adapter"))?;
-
-        let device_queue = adapter.request_device(
-            &DeviceDescriptor {
-                label: None,
-                required_features: Features::empty(),
-                required_limits: Limits::downlevel_defaults(),
-            },
-            None,
-        );
-
-        let (device, queue) = pollster::block_on(device_queue).map_err(|e| e.to_string())?;
+        let instance = Self::create_instance(configuration);
+        let surface = instance
+            .create_surface(window)
+            .map_err(RendererError::SurfaceCreation)?;
+        let (adapter, device, queue) =
+            Self::request_device(&instance, configuration, Some(&surface))?;
         let capabilities = surface.get_capabilities(&adapter);
         let desired_surface_formats = {
             let mut h = HashSet::new();
@@ -83,71 +103,196 @@ impl<'a> Renderer<'a> {
             .formats
             .iter()
             .find(|f| desired_surface_formats.contains(f))
-            .ok_or(String::from("Failed to acquire compatible surface"))?;
-
-        let surface_configuration = if capabilities.present_modes.contains(&PresentMode::Mailbox) {
-            wgpu::SurfaceConfiguration {
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                format: *format,
-                width: configuration.window_width,
-                height: configuration.window_height,
-                present_mode: wgpu::PresentMode::Mailbox,
-                alpha_mode: wgpu::CompositeAlphaMode::Auto,
-                view_formats: [].to_vec(),
-                desired_maximum_frame_latency: 2,
-            }
+            .ok_or(RendererError::IncompatibleSurface)?;
+
+        // Frame capture copies out of the surface texture when the platform allows it
+        let usage = if capabilities.usages.contains(wgpu::TextureUsages::COPY_SRC) {
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC
         } else {
-            wgpu::SurfaceConfiguration {
-                usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-                format: *format,
-                width: configuration.window_width,
-                height: configuration.window_height,
-                present_mode: wgpu::PresentMode::Fifo,
-                alpha_mode: wgpu::CompositeAlphaMode::Auto,
-                view_formats: [].to_vec(),
-                desired_maximum_frame_latency: 2,
+            wgpu::TextureUsages::RENDER_ATTACHMENT
+        };
+
+        let present_mode = match configuration.present_mode {
+            Some(present_mode) => present_mode,
+            None if capabilities.present_modes.contains(&PresentMode::Mailbox) => {
+                PresentMode::Mailbox
             }
+            None => PresentMode::Fifo,
+        };
+        if !render_target::supports_present_mode(&capabilities.present_modes, present_mode) {
+            return Err(RendererError::UnsupportedPresentMode(present_mode));
+        }
+
+        if configuration.alpha_mode != CompositeAlphaMode::Auto
+            && !capabilities.alpha_modes.contains(&configuration.alpha_mode)
+        {
+            return Err(RendererError::UnsupportedAlphaMode(
+                configuration.alpha_mode,
+            ));
+        }
+
+        let surface_configuration = wgpu::SurfaceConfiguration {
+            usage,
+            format: *format,
+            width: configuration.window_width,
+            height: configuration.window_height,
+            present_mode,
+            alpha_mode: configuration.alpha_mode,
+            view_formats: [].to_vec(),
+            desired_maximum_frame_latency: configuration.maximum_frame_latency,
         };
 
         surface.configure(&device, &surface_configuration);
 
-        let depth_texture = Texture::new_depth_texture(
+        let target = RenderTarget::Surface {
+            surface,
+            configuration: surface_configuration,
+            present_modes: capabilities.present_modes,
+            texture: None,
+            view: None,
+            reconfigure: false,
+        };
+
+        Ok(Self::from_parts(target, device, queue, configuration))
+    }
+
+    // Creates a renderer that draws into an offscreen color target instead of
+    // a window surface. Software adapters (lavapipe, llvmpipe) are used when
+    // no hardware adapter is available.
+    pub fn new_headless(configuration: &RendererConfiguration) -> Result<Self, RendererError> {
+        let instance = Self::create_instance(configuration);
+        let (_adapter, device, queue) = Self::request_device(&instance, configuration, None)?;
+
+        let color_texture = Texture::new_render_target(
             &device,
             configuration.window_width,
             configuration.window_height,
-            "depth_texture",
-            &Renderer::DEPTH_FORMAT,
+            "color_texture",
+            &Renderer::OFFSCREEN_FORMAT,
         );
 
-        let output = match surface.get_current_texture() {
-            Ok(output) => output,
-            Err(err) => panic!("Could not get surface for rendering: {}", err),
+        let target = RenderTarget::Offscreen {
+            color_texture,
+            format: Renderer::OFFSCREEN_FORMAT,
         };
 
-        let view = output.texture.create_view(&wgpu::TextureViewDescriptor {
-            label: None,
-            format: Some(surface_configuration.format),
-            dimension: None,
-            aspect: wgpu::TextureAspect::All,
-            base_mip_level: 0,
-            mip_level_count: None,
-            base_array_layer: 0,
-            array_layer_count: None,
-        });
+        Ok(Self::from_parts(target, device, queue, configuration))
+    }
 
-        Ok(Self {
-            surface,
-            surface_configuration,
+    // Lists the adapters available for the configured backends, for example
+    // to find the name of a software adapter to put in adapter_name
+    pub fn enumerate_adapters(configuration: &RendererConfiguration) -> Vec<AdapterInfo> {
+        Self::create_instance(configuration)
+            .enumerate_adapters(configuration.backends)
+            .iter()
+            .map(|adapter| adapter.get_info())
+            .collect()
+    }
+
+    fn create_instance(configuration: &RendererConfiguration) -> Instance {
+        Instance::new(InstanceDescriptor {
+            backends: configuration.backends,
+            flags: configuration.instance_flags,
+            dx12_shader_compiler: configuration.dx12_shader_compiler.clone(),
+            gles_minor_version: Gles3MinorVersion::Automatic,
+        })
+    }
+
+    fn request_adapter(
+        instance: &Instance,
+        configuration: &RendererConfiguration,
+        compatible_surface: Option<&Surface>,
+    ) -> Option<Adapter> {
+        if let Some(name) = &configuration.adapter_name {
+            return instance
+                .enumerate_adapters(configuration.backends)
+                .into_iter()
+                .filter(|adapter| adapter.get_info().name.contains(name.as_str()))
+                .find(|adapter| match compatible_surface {
+                    Some(surface) => adapter.is_surface_supported(surface),
+                    None => true,
+                });
+        }
+
+        let adapter = pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
+            power_preference: configuration.power_preference,
+            force_fallback_adapter: configuration.force_fallback_adapter,
+            compatible_surface,
+        }));
+
+        // Machines without a GPU may only expose a software fallback adapter
+        match adapter {
+            Some(adapter) => Some(adapter),
+            None if !configuration.force_fallback_adapter => {
+                pollster::block_on(instance.request_adapter(&RequestAdapterOptions {
+                    power_preference: configuration.power_preference,
+                    force_fallback_adapter: true,
+                    compatible_surface,
+                }))
+            }
+            None => None,
+        }
+    }
+
+    fn request_device(
+        instance: &Instance,
+        configuration: &RendererConfiguration,
+        compatible_surface: Option<&Surface>,
+    ) -> Result<(Adapter, Device, Queue), RendererError> {
+        let adapter = Self::request_adapter(instance, configuration, compatible_surface)
+            .ok_or(RendererError::AdapterRequest)?;
+
+        let device_queue = adapter.request_device(
+            &DeviceDescriptor {
+                label: None,
+                required_features: configuration.required_features,
+                required_limits: configuration.required_limits.clone(),
+            },
+            None,
+        );
+
+        let (device, queue) =
+            pollster::block_on(device_queue).map_err(RendererError::DeviceRequest)?;
+        Ok((adapter, device, queue))
+    }
+
+    fn from_parts(
+        target: RenderTarget<'a>,
+        device: Device,
+        queue: Queue,
+        configuration: &RendererConfiguration,
+    ) -> Self {
+        let depth_texture = Texture::new_depth_texture(
+            &device,
+            configuration.window_width,
+            configuration.window_height,
+            "depth_texture",
+            &Renderer::DEPTH_FORMAT,
+        );
+
+        Self {
+            target,
             device,
             queue,
             depth_texture,
-            pipelines: SlotMap::with_capacity(12),
+            pipelines: HandleMap::with_capacity(12),
             pipeline_lookup: HashMap::new(),
-            mesh_cache: SlotMap::with_capacity(12),
+            bind_group_layouts: BindGroupLayoutCache::new(),
+            mesh_cache: HandleMap::with_capacity(12),
             material_cache: MaterialCache::new(),
-            surface_texture: Some(output),
-            surface_view: Some(view),
-        })
+            retirement_queue: RetirementQueue::new(),
+            frame_encoder: FrameEncoder::new(configuration.submission_policy.clone()),
+            frame_number: 0,
+            last_frame_statistics: FrameStatistics::default(),
+            viewport: None,
+            clear_pending: true,
+            clear_color: configuration.clear_color,
+            clear_depth: configuration.clear_depth,
+            paused: false,
+            pending_size: None,
+            shader_reload_interval: configuration.shader_reload_interval,
+            last_shader_reload: Instant::now(),
+        }
     }
 
     // Functions to preload meshes, materials and pipelines.
@@ -155,138 +300,574 @@ impl<'a> Renderer<'a> {
     pub fn register_pipeline(
         &mut self,
         configuration: &PipelineConfiguration,
-    ) -> Result<(), String> {
+    ) -> Result<PipelineHandle, RendererError> {
+        // Identical configurations share one pipeline
+        if let Some(handle) = self.pipeline_lookup.get(configuration) {
+            return Ok(*handle);
+        }
+
         let pipeline = Pipeline::from_configuration(
             configuration.clone(),
             &self.device,
-            &self.surface_configuration.format,
+            &self.target.format(),
+            &mut self.bind_group_layouts,
         )?;
 
-        let index = self.pipelines.push(pipeline);
-        self.pipeline_lookup.insert(configuration.clone(), index);
-        Ok(())
+        let handle = self.pipelines.insert(pipeline);
+        self.pipeline_lookup.insert(configuration.clone(), handle);
+        Ok(handle)
     }
 
     // Having separate mesh and material registration might be
     // problematic.
-    pub fn register_mesh(&mut self, mesh_source: &MeshSource) -> MeshHandle {
-        let mesh = Mesh::from_source(&self.device, &self.pipeline_lookup, mesh_source);
-        self.mesh_cache.push(mesh)
+    pub fn register_mesh(&mut self, mesh_source: &MeshSource) -> Result<MeshHandle, RendererError> {
+        let mesh = Mesh::from_source(
+            &self.device,
+            &self.pipelines,
+            &self.material_cache,
+            &mut self.bind_group_layouts,
+            mesh_source,
+        )?;
+        let pipeline = *mesh.pipeline();
+        let handle = self.mesh_cache.insert(mesh);
+        if let Some(pipeline) = self.pipelines.get_mut(&pipeline) {
+            pipeline.add_mesh(handle);
+        }
+        Ok(handle)
     }
 
-    pub fn register_material(&mut self, material_source: &MaterialSource) {
-        let material = Material::from_source(material_source, &self.device, &self.queue);
-        self.material_cache.insert(material.id(), material);
+    pub fn register_material(
+        &mut self,
+        material_source: &MaterialSource,
+    ) -> Result<MaterialHandle, RendererError> {
+        let material = Material::from_source(
+            material_source,
+            &self.device,
+            &self.queue,
+            &mut self.bind_group_layouts,
+        )?;
+        Ok(self.material_cache.insert(material))
     }
 
-    pub fn unregister_mesh() {
-        todo!()
+    // Unregistered resources stay alive until the frames that may still use
+    // them have finished on the GPU
+    pub fn unregister_mesh(&mut self, mesh_handle: MeshHandle) -> Result<(), RendererError> {
+        let mesh = self
+            .mesh_cache
+            .remove(&mesh_handle)
+            .ok_or(RendererError::InvalidHandle)?;
+
+        if let Some(pipeline) = self.pipelines.get_mut(mesh.pipeline()) {
+            pipeline.retain_queued(|queued| *queued != mesh_handle);
+            pipeline.remove_mesh(mesh_handle);
+        }
+
+        self.retirement_queue
+            .retire(RetiredResource::Mesh { _mesh: mesh });
+        Ok(())
     }
-    pub fn unregister_material() {
-        todo!()
+
+    pub fn unregister_material(
+        &mut self,
+        material_handle: MaterialHandle,
+    ) -> Result<(), RendererError> {
+        let material = self
+            .material_cache
+            .remove(&material_handle)
+            .ok_or(RendererError::InvalidHandle)?;
+
+        let mesh_cache = &self.mesh_cache;
+        for pipeline in self.pipelines.iter_mut() {
+            pipeline.retain_queued(|queued| {
+                mesh_cache
+                    .get(queued)
+                    .is_some_and(|mesh| !mesh.uses_material(&material_handle))
+            });
+        }
+
+        self.retirement_queue.retire(RetiredResource::Material {
+            _material: material,
+        });
+        Ok(())
     }
 
-    pub fn submit_mesh(&mut self, mesh_handle: MeshHandle) {
-        let mesh = self.mesh_cache.get(&mesh_handle).unwrap();
+    pub fn submit_mesh(
+        &mut self,
+        frame: &mut Frame,
+        mesh_handle: MeshHandle,
+    ) -> Result<(), RendererError> {
+        let mesh = self
+            .mesh_cache
+            .get(&mesh_handle)
+            .ok_or(RendererError::InvalidHandle)?;
+        if !self.pipelines.contains(mesh.pipeline()) {
+            return Err(RendererError::UnknownPipeline);
+        }
+        mesh.validate_materials(&self.material_cache)?;
 
-        self.pipelines
-            .get_mut(mesh.pipeline())
-            .unwrap()
-            .submit_mesh(
-                &self.device,
-                &self.queue,
-                self.surface_view.as_ref().unwrap(),
-                self.depth_texture.view(),
-                mesh_handle,
-                &self.mesh_cache,
-                &self.material_cache,
-            );
+        let pipeline_handle = *mesh.pipeline();
+
+        if !self.prepare_target(frame)? {
+            return Ok(());
+        }
+
+        let queued_draws = self
+            .pipelines
+            .get_mut(&pipeline_handle)
+            .ok_or(RendererError::UnknownPipeline)?
+            .queue_mesh(mesh_handle);
+
+        // Check the heuristic for submission
+        if self.frame_encoder.should_record(queued_draws) {
+            self.record(Some(pipeline_handle))?;
+        }
+        Ok(())
     }
 
     pub fn add_pipeline_global(
         &mut self,
-        pipeline: &PipelineConfiguration,
+        pipeline: PipelineHandle,
         uniform_group: &UniformGroupSource,
-    ) {
-        let pipeline = self.pipeline_lookup.get(pipeline).unwrap();
-        let pipeline = self.pipelines.get_mut(pipeline).unwrap();
-        pipeline.add_global_bind_group(uniform_group, &self.device);
+    ) -> Result<(), RendererError> {
+        let pipeline = self
+            .pipelines
+            .get_mut(&pipeline)
+            .ok_or(RendererError::UnknownPipeline)?;
+        pipeline.add_global_bind_group(uniform_group, &self.device, &mut self.bind_group_layouts)
+    }
+
+    // Replaces the contents of a uniform of a mesh's uniform group. The data
+    // must be as long as the uniform source it was created from. Draws
+    // submitted so far keep the old contents and the following ones see the
+    // new ones, the write is ordered between them in the frame's encoder.
+    pub fn update_mesh_uniform(
+        &mut self,
+        mesh_handle: MeshHandle,
+        index: usize,
+        data: &[u8],
+    ) -> Result<(), RendererError> {
+        let pipeline = *self
+            .mesh_cache
+            .get(&mesh_handle)
+            .ok_or(RendererError::InvalidHandle)?
+            .pipeline();
+        self.record(Some(pipeline))?;
+
+        let mesh = self
+            .mesh_cache
+            .get(&mesh_handle)
+            .ok_or(RendererError::InvalidHandle)?;
+        mesh.mesh_uniform_group().write_uniform(
+            &self.device,
+            &self.queue,
+            &mut self.frame_encoder,
+            index,
+            data,
+        )
+    }
+
+    // Replaces the contents of a uniform in one of a pipeline's global
+    // groups, indexed in the order they were added. Ordering is the same as
+    // for update_mesh_uniform.
+    pub fn update_pipeline_global(
+        &mut self,
+        pipeline: PipelineHandle,
+        group: usize,
+        index: usize,
+        data: &[u8],
+    ) -> Result<(), RendererError> {
+        if !self.pipelines.contains(&pipeline) {
+            return Err(RendererError::UnknownPipeline);
+        }
+        self.record(Some(pipeline))?;
+
+        let uniform_group = self
+            .pipelines
+            .get(&pipeline)
+            .ok_or(RendererError::UnknownPipeline)?
+            .global_bind_group(group)
+            .ok_or(RendererError::UnknownUniform)?;
+        uniform_group.write_uniform(
+            &self.device,
+            &self.queue,
+            &mut self.frame_encoder,
+            index,
+            data,
+        )
+    }
+
+    // Typed forms of update_mesh_uniform and update_pipeline_global. The
+    // value's layout is checked like TypedUniform::new, and a value whose
+    // size differs from the uniform is an error.
+    pub fn update_mesh_uniform_typed<T: WgslType>(
+        &mut self,
+        mesh_handle: MeshHandle,
+        index: usize,
+        value: &T,
+    ) -> Result<(), RendererError> {
+        self.update_mesh_uniform(mesh_handle, index, TypedUniform::new(*value).bytes())
     }
 
-    pub fn render(&mut self) {
-        // Force all pipelines to submit now
-        for pipeline in &mut self.pipelines {
-            pipeline.flush_queue(
+    pub fn update_pipeline_global_typed<T: WgslType>(
+        &mut self,
+        pipeline: PipelineHandle,
+        group: usize,
+        index: usize,
+        value: &T,
+    ) -> Result<(), RendererError> {
+        self.update_pipeline_global(pipeline, group, index, TypedUniform::new(*value).bytes())
+    }
+
+    // Recompiles the pipelines whose shader files changed since they were
+    // compiled and returns how many were replaced. Handles stay valid, and a
+    // pipeline whose new shaders fail to compile keeps its old ones.
+    pub fn reload_shaders(&mut self) -> usize {
+        self.last_shader_reload = Instant::now();
+        let format = self.target.format();
+        let mut reloaded = 0;
+        for pipeline in self.pipelines.iter_mut() {
+            if !pipeline.shaders_changed() {
+                continue;
+            }
+            match pipeline.reload(
                 &self.device,
-                &self.queue,
-                self.surface_view.as_ref().unwrap(),
-                self.depth_texture.view(),
+                &format,
+                &mut self.bind_group_layouts,
                 &self.mesh_cache,
                 &self.material_cache,
-            )
+            ) {
+                Ok(()) => reloaded += 1,
+                Err(error) => {
+                    log::error!("Failed to reload shaders, keeping the old ones: {}", error)
+                }
+            }
         }
+        reloaded
+    }
 
-        {
-            // Take and present the built surface
-            let texture = self.surface_texture.take().unwrap();
-            self.surface_view.take().unwrap();
-            texture.present();
-        }
-
-        // Reacquire surfaces
-        let texture = self
-            .surface
-            .get_current_texture()
-            .expect("Could not get next frame buffer");
-
-        let view = texture.texture.create_view(&wgpu::TextureViewDescriptor {
-            label: None,
-            format: Some(self.surface_configuration.format),
-            dimension: None,
-            aspect: wgpu::TextureAspect::All,
-            base_mip_level: 0,
-            mip_level_count: None,
-            base_array_layer: 0,
-            array_layer_count: None,
-        });
+    // Starts a frame. Nothing is acquired or cleared until the frame first
+    // draws, which happens at the latest in end_frame.
+    pub fn begin_frame(&mut self) -> Frame {
+        if let Some(interval) = self.shader_reload_interval {
+            if self.last_shader_reload.elapsed() >= interval {
+                self.reload_shaders();
+            }
+        }
 
-        self.surface_texture = Some(texture);
-        self.surface_view = Some(view);
-
-        // Provide a basic clear op immediately
-        // It is unlikely that the GPU is busy at this point so there should
-        // be low performance impact
-        let mut encoder = self
-            .device
-            .create_command_encoder(&CommandEncoderDescriptor { label: None });
-        encoder.begin_render_pass(&RenderPassDescriptor {
-            label: Some("Clear"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view: self.surface_view.as_ref().unwrap(),
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(Color {
-                        r: 0.6,
-                        g: 0.6,
-                        b: 0.6,
-                        a: 0.6,
-                    }),
-                    store: StoreOp::Store,
-                },
-            })],
-            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
-                view: self.depth_texture.view(),
-                depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
-                    store: StoreOp::Store,
-                }),
-                stencil_ops: None,
-            }),
-            timestamp_writes: None,
-            occlusion_query_set: None,
-        });
+        self.clear_pending = true;
+        self.viewport = None;
+        self.frame_number += 1;
+        Frame::new(self.frame_number)
+    }
+
+    // Draws everything still queued and presents the frame
+    pub fn end_frame(&mut self, mut frame: Frame) -> Result<FrameStatus, RendererError> {
+        if !self.prepare_target(&mut frame)? {
+            self.discard_frame();
+            self.end_frame_resize();
+            self.retirement_queue.end_frame(&self.device, &self.queue);
+            return Ok(FrameStatus::Skipped);
+        }
+
+        // Present even if recording failed so the surface texture is released
+        let recorded = self.record(None);
+        self.last_frame_statistics = self.frame_encoder.end_frame(&self.queue);
+        self.target.present(&self.device);
+        self.end_frame_resize();
+        self.retirement_queue.end_frame(&self.device, &self.queue);
+        recorded.map(|_| FrameStatus::Presented)
+    }
+
+    // Draws the debug primitives over everything submitted to the frame so
+    // far. Call once all scene geometry of the frame (or view) is submitted.
+    pub fn draw_debug(
+        &mut self,
+        frame: &mut Frame,
+        debug_draw: &mut DebugDraw,
+    ) -> Result<(), RendererError> {
+        if !self.prepare_target(frame)? {
+            return Ok(());
+        }
+        self.record(None)?;
+
+        let mut target = DrawTarget {
+            color: self.target.view(),
+            depth: self.depth_texture.view(),
+            viewport: frame.viewport,
+            clear_pending: self.clear_pending,
+            clear_color: self.clear_color,
+            clear_depth: self.clear_depth,
+        };
+        let viewport_size = match frame.viewport {
+            Some(viewport) => Vec2::new(viewport.width, viewport.height),
+            None => {
+                let (width, height) = self.target.size();
+                Vec2::new(width as f32, height as f32)
+            }
+        };
+        let encoder = self.frame_encoder.encoder(&self.device);
+        let draws = debug_draw.record(
+            &self.device,
+            &self.queue,
+            encoder,
+            &mut target,
+            frame.number,
+            viewport_size,
+        );
+        self.clear_pending = target.clear_pending;
+        if draws > 0 {
+            self.frame_encoder.end_pass(&self.queue, draws);
+        }
+        Ok(())
+    }
+
+    // Counters for the last frame passed to end_frame
+    pub fn last_frame_statistics(&self) -> FrameStatistics {
+        self.last_frame_statistics
+    }
+
+    pub fn submission_policy(&self) -> &dyn SubmissionPolicy {
+        self.frame_encoder.policy()
+    }
+
+    // Takes effect from the next draw, work already recorded is unaffected
+    pub fn set_submission_policy(&mut self, policy: Box<dyn SubmissionPolicy>) {
+        self.frame_encoder.set_policy(policy);
+    }
+
+    // Restricts the following draws of the frame to a region of the target,
+    // None covers the whole target. Draws queued so far keep the previous
+    // viewport.
+    pub fn set_viewport(
+        &mut self,
+        frame: &mut Frame,
+        viewport: Option<Viewport>,
+    ) -> Result<(), RendererError> {
+        if let Some(viewport) = viewport {
+            let (width, height) = self.size();
+            if viewport.x < 0.0
+                || viewport.y < 0.0
+                || viewport.width <= 0.0
+                || viewport.height <= 0.0
+                || viewport.x + viewport.width > width as f32
+                || viewport.y + viewport.height > height as f32
+                || !(0.0..=1.0).contains(&viewport.min_depth)
+                || !(viewport.min_depth..=1.0).contains(&viewport.max_depth)
+            {
+                return Err(RendererError::InvalidViewport(viewport));
+            }
+        }
+
+        self.record(None)?;
+        self.viewport = viewport;
+        frame.viewport = viewport;
+        Ok(())
+    }
+
+    // Resizes the color target and depth buffer. A zero sized window (such as
+    // a minimized one) pauses rendering until it is resized again. While the
+    // surface texture of a frame is held the targets are resized once the
+    // frame ends.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        if width == 0 || height == 0 {
+            self.paused = true;
+            return;
+        }
+
+        self.paused = false;
+        if self.target.holds_texture() {
+            self.pending_size = Some((width, height));
+            return;
+        }
+        self.pending_size = None;
+        self.resize_targets(width, height);
+    }
+
+    fn end_frame_resize(&mut self) {
+        if let Some((width, height)) = self.pending_size.take() {
+            self.resize_targets(width, height);
+        }
+    }
+
+    fn resize_targets(&mut self, width: u32, height: u32) {
+        if self.size() == (width, height) {
+            return;
+        }
+
+        self.target.resize(&self.device, width, height);
+        self.depth_texture = Texture::new_depth_texture(
+            &self.device,
+            width,
+            height,
+            "depth_texture",
+            &Renderer::DEPTH_FORMAT,
+        );
+        self.clear_pending = true;
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        self.target.size()
+    }
+
+    pub(crate) fn device(&self) -> &Device {
+        &self.device
+    }
+
+    pub(crate) fn queue(&self) -> &Queue {
+        &self.queue
+    }
+
+    #[cfg(feature = "testing")]
+    pub(crate) fn bind_group_layout_count(&self) -> usize {
+        self.bind_group_layouts.len()
+    }
+
+    // Queues a draw without the checks of submit_mesh
+    #[cfg(feature = "testing")]
+    pub(crate) fn queue_unchecked(&mut self, pipeline: PipelineHandle, mesh: MeshHandle) {
+        if let Some(pipeline) = self.pipelines.get_mut(&pipeline) {
+            pipeline.queue_mesh(mesh);
+        }
+    }
+
+    pub(crate) fn target_format(&self) -> TextureFormat {
+        self.target.format()
+    }
+
+    // Headless renderers have no present mode
+    pub fn present_mode(&self) -> Option<PresentMode> {
+        self.target.present_mode()
+    }
+
+    // Reconfigures the surface to present with the given mode, for example to
+    // toggle vsync at runtime. Takes effect from the next frame when the
+    // current one holds a surface texture. Has no effect on headless renderers.
+    pub fn set_present_mode(&mut self, present_mode: PresentMode) -> Result<(), RendererError> {
+        self.target.set_present_mode(&self.device, present_mode)
+    }
+
+    // Changes take effect from the next frame that has not started drawing
+    pub fn set_clear_color(&mut self, clear_color: Option<Color>) {
+        self.clear_color = clear_color;
+    }
+
+    pub fn clear_color(&self) -> Option<Color> {
+        self.clear_color
+    }
+
+    pub fn set_clear_depth(&mut self, clear_depth: Option<f32>) {
+        self.clear_depth = clear_depth;
+    }
+
+    pub fn clear_depth(&self) -> Option<f32> {
+        self.clear_depth
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    // Copies the contents of the frame drawn so far into an image. Pending
+    // draws are flushed first. Fails when the frame is skipped or the surface
+    // does not allow copying from its textures.
+    pub fn capture_frame(&mut self, frame: &mut Frame) -> Result<RgbaImage, RendererError> {
+        if self.paused {
+            return Err(RendererError::RenderingPaused);
+        }
+
+        if !self.prepare_target(frame)? {
+            return Err(RendererError::FrameSkipped);
+        }
+
+        self.record(None)?;
+        self.frame_encoder.submit(&self.queue);
+        frame_capture::capture_texture(
+            &self.device,
+            &self.queue,
+            self.target.texture(),
+            self.target.format(),
+        )
+    }
+
+    // Drops everything drawn in a frame that is skipped after drawing began,
+    // for example when the window is minimized mid-frame, so none of it leaks
+    // into the next frame
+    fn discard_frame(&mut self) {
+        for pipeline in self.pipelines.iter_mut() {
+            pipeline.retain_queued(|_| false);
+        }
+        self.frame_encoder.discard();
+        self.target.discard(&self.device);
+        self.last_frame_statistics = FrameStatistics::default();
+    }
+
+    // Acquires the target for the frame. Returns false when nothing should be
+    // drawn this frame.
+    fn prepare_target(&mut self, frame: &mut Frame) -> Result<bool, RendererError> {
+        if self.paused || frame.skipped {
+            return Ok(false);
+        }
+
+        if !self.target.acquire(&self.device)? {
+            log::warn!("No surface texture available, skipping frame");
+            frame.skipped = true;
+            return Ok(false);
+        }
+        Ok(true)
+    }
+
+    // Records the draws queued on one pipeline, or on all of them, into the
+    // frame encoder. The target is cleared by the first pass of the frame, or
+    // by an empty pass when all pipelines are recorded and nothing was drawn.
+    fn record(&mut self, pipeline: Option<PipelineHandle>) -> Result<(), RendererError> {
+        // Nothing can be queued before the target is acquired
+        if !self.target.is_acquired() {
+            return Ok(());
+        }
+
+        let mut target = DrawTarget {
+            color: self.target.view(),
+            depth: self.depth_texture.view(),
+            viewport: self.viewport,
+            clear_pending: self.clear_pending,
+            clear_color: self.clear_color,
+            clear_depth: self.clear_depth,
+        };
+
+        let mut result = Ok(());
+        let pipelines: Vec<&mut Pipeline> = match pipeline {
+            Some(handle) => self.pipelines.get_mut(&handle).into_iter().collect(),
+            None => self.pipelines.iter_mut().collect(),
+        };
+        for pipeline in pipelines {
+            // Avoid creating an encoder that would be submitted empty
+            if pipeline.queued_draws() == 0 {
+                continue;
+            }
+
+            let encoder = self.frame_encoder.encoder(&self.device);
+            let recorded =
+                pipeline.record_queue(encoder, &mut target, &self.mesh_cache, &self.material_cache);
+            match recorded {
+                Ok(0) => {}
+                Ok(draws) => self.frame_encoder.end_pass(&self.queue, draws),
+                // Keep recording the other pipelines so none of their draws
+                // are left queued for the next frame, and report the first error
+                Err(error) => {
+                    if result.is_ok() {
+                        result = Err(error);
+                    }
+                }
+            }
+        }
+
+        if pipeline.is_none() && target.clear_pending {
+            let encoder = self.frame_encoder.encoder(&self.device);
+            drop(target.begin_render_pass(encoder, "clear"));
+            self.frame_encoder.end_pass(&self.queue, 0);
+        }
 
-        let commands = encoder.finish();
-        self.queue.submit([commands]);
+        self.clear_pending = target.clear_pending;
+        result
     }
 }
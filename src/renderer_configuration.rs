@@ -1,9 +1,60 @@
+use crate::submission_policy::{FixedCount, SubmissionPolicy};
 use derive_builder::Builder;
-use wgpu::InstanceFlags;
+use std::time::Duration;
+use wgpu::{
+    Backends, Color, CompositeAlphaMode, Dx12Compiler, Features, InstanceFlags, Limits,
+    PowerPreference, PresentMode,
+};
 
-#[derive(Debug, Copy, Clone, Builder)]
+#[derive(Debug, Clone, Builder)]
 pub struct RendererConfiguration {
     pub instance_flags: InstanceFlags, // Codependency
     pub window_width: u32,
     pub window_height: u32,
+
+    // Adapter selection
+    #[builder(default = "Backends::VULKAN")]
+    pub backends: Backends,
+    #[builder(default = "PowerPreference::HighPerformance")]
+    pub power_preference: PowerPreference,
+    #[builder(default = "false")]
+    pub force_fallback_adapter: bool,
+    // Picks the first adapter whose name contains this string, see
+    // Renderer::enumerate_adapters for the names available
+    #[builder(default = "None")]
+    pub adapter_name: Option<String>,
+    #[builder(default = "Dx12Compiler::Fxc")]
+    pub dx12_shader_compiler: Dx12Compiler,
+
+    // Device creation
+    #[builder(default = "Features::empty()")]
+    pub required_features: Features,
+    #[builder(default = "Limits::downlevel_defaults()")]
+    pub required_limits: Limits,
+
+    // Surface presentation, ignored by headless renderers
+    // When no present mode is given Mailbox is used if available, else Fifo
+    #[builder(default = "None")]
+    pub present_mode: Option<PresentMode>,
+    #[builder(default = "2")]
+    pub maximum_frame_latency: u32,
+    #[builder(default = "CompositeAlphaMode::Auto")]
+    pub alpha_mode: CompositeAlphaMode,
+
+    // Values the color and depth targets are cleared to at the start of each
+    // frame. None keeps the previous contents instead. To composite over other
+    // content clear to Color::TRANSPARENT with a non opaque alpha_mode.
+    #[builder(default = "Some(Color { r: 0.6, g: 0.6, b: 0.6, a: 0.6 })")]
+    pub clear_color: Option<Color>,
+    #[builder(default = "Some(1.0)")]
+    pub clear_depth: Option<f32>,
+
+    // Decides when queued draws are recorded and submitted to the GPU
+    #[builder(default = "Box::new(FixedCount::default())")]
+    pub submission_policy: Box<dyn SubmissionPolicy>,
+
+    // How often begin_frame checks the shader files of registered pipelines
+    // for changes and reloads them. None only reloads on reload_shaders.
+    #[builder(default = "None")]
+    pub shader_reload_interval: Option<Duration>,
 }
@@ -0,0 +1,54 @@
+use crate::{material::Material, mesh::Mesh};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use wgpu::{Device, Maintain, Queue};
+
+// Resources are never read again, they are only held to delay their drop
+pub enum RetiredResource {
+    Mesh { _mesh: Mesh },
+    Material { _material: Material },
+}
+
+// Holds unregistered resources until the GPU has finished every frame that
+// could still reference them. Frames are counted by the renderer and the
+// queue reports back once the work submitted up to the end of a frame is done.
+pub struct RetirementQueue {
+    current_frame: u64,
+    completed_frames: Arc<AtomicU64>,
+    retired: Vec<(u64, RetiredResource)>,
+}
+
+impl RetirementQueue {
+    pub fn new() -> Self {
+        Self {
+            current_frame: 0,
+            completed_frames: Arc::new(AtomicU64::new(0)),
+            retired: Vec::new(),
+        }
+    }
+
+    pub fn retire(&mut self, resource: RetiredResource) {
+        self.retired.push((self.current_frame, resource));
+    }
+
+    // Called once all of the current frame's work has been submitted
+    pub fn end_frame(&mut self, device: &Device, queue: &Queue) {
+        let completed_frames = self.completed_frames.clone();
+        let frame_count = self.current_frame + 1;
+        queue.on_submitted_work_done(move || {
+            completed_frames.fetch_max(frame_count, Ordering::AcqRel);
+        });
+        self.current_frame = frame_count;
+
+        // Run completion callbacks without blocking on the GPU
+        device.poll(Maintain::Poll);
+        self.reclaim();
+    }
+
+    fn reclaim(&mut self) {
+        let completed_frames = self.completed_frames.load(Ordering::Acquire);
+        self.retired.retain(|(frame, _)| *frame >= completed_frames);
+    }
+}
@@ -0,0 +1,5 @@
+// WGSL shaders embedded in the crate for the built-in pipeline configurations
+pub const UNLIT_COLOR: &str = include_str!("shaders/unlit_color.wgsl");
+pub const UNLIT_TEXTURED: &str = include_str!("shaders/unlit_textured.wgsl");
+pub const VERTEX_COLOR: &str = include_str!("shaders/vertex_color.wgsl");
+pub const LIT: &str = include_str!("shaders/lit.wgsl");
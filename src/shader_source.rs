@@ -0,0 +1,176 @@
+use crate::error::{RendererError, ShaderError};
+use std::{
+    borrow::Cow,
+    path::{Path, PathBuf},
+};
+use wgpu::{
+    naga::{
+        front::wgsl,
+        valid::{Capabilities, ValidationFlags, Validator},
+        Module, ShaderStage, SourceLocation,
+    },
+    Device, ShaderModule, ShaderModuleDescriptor,
+};
+
+// Where the code of a pipeline's shaders comes from. Inline sources compare
+// and hash by their contents so identical configurations still share one
+// pipeline.
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum ShaderSource {
+    // A WGSL file read when the pipeline is created
+    Path(PathBuf),
+    // WGSL code, embedded with include_str! or generated at runtime
+    Wgsl(Cow<'static, str>),
+    // A SPIR-V binary in either byte order
+    #[cfg(feature = "spirv")]
+    SpirV(Cow<'static, [u8]>),
+    // A GLSL module only holds a single stage, so pipelines using one need
+    // a separate fragment shader source
+    #[cfg(feature = "glsl")]
+    Glsl {
+        source: Cow<'static, str>,
+        stage: ShaderStage,
+    },
+}
+
+impl ShaderSource {
+    pub fn label(&self) -> String {
+        match self {
+            ShaderSource::Path(path) => path.display().to_string(),
+            ShaderSource::Wgsl(_) => String::from("wgsl"),
+            #[cfg(feature = "spirv")]
+            ShaderSource::SpirV(_) => String::from("spirv"),
+            #[cfg(feature = "glsl")]
+            ShaderSource::Glsl { stage, .. } => format!("glsl({:?})", stage),
+        }
+    }
+
+    // The file the source is read from, if any
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            ShaderSource::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    // Reads WGSL sources and checks them with naga. Other sources are left to
+    // the device and return None.
+    pub(crate) fn parse_wgsl(
+        &self,
+        label: &str,
+    ) -> Result<Option<(Cow<'static, str>, Module)>, RendererError> {
+        let (source, file) = match self {
+            ShaderSource::Path(path) => {
+                let source =
+                    std::fs::read_to_string(path).map_err(|error| RendererError::ShaderLoad {
+                        path: path.display().to_string(),
+                        error,
+                    })?;
+                (Cow::Owned(source), path.display().to_string())
+            }
+            ShaderSource::Wgsl(source) => (source.clone(), label.to_string()),
+            #[cfg(feature = "spirv")]
+            ShaderSource::SpirV(_) => return Ok(None),
+            #[cfg(feature = "glsl")]
+            ShaderSource::Glsl { .. } => return Ok(None),
+        };
+        let module = validate_wgsl(&source, &file)?;
+        Ok(Some((source, module)))
+    }
+
+    // Creates the module the given pipeline stages are taken from, along with
+    // its naga module when the source is WGSL
+    #[cfg_attr(not(feature = "glsl"), allow(unused_variables))]
+    pub(crate) fn create_module(
+        &self,
+        device: &Device,
+        label: &str,
+        stages: &[ShaderStage],
+    ) -> Result<(ShaderModule, Option<Module>), RendererError> {
+        let (source, module) = match self {
+            ShaderSource::Path(_) | ShaderSource::Wgsl(_) => match self.parse_wgsl(label)? {
+                Some((source, module)) => (wgpu::ShaderSource::Wgsl(source), Some(module)),
+                None => unreachable!("WGSL sources are always parsed"),
+            },
+            #[cfg(feature = "spirv")]
+            ShaderSource::SpirV(bytes) => {
+                // make_spirv_raw panics on malformed input
+                const MAGIC_NUMBER: u32 = 0x0723_0203;
+                let magic = bytes
+                    .get(..4)
+                    .map(|magic| u32::from_ne_bytes([magic[0], magic[1], magic[2], magic[3]]));
+                if bytes.len() % 4 != 0
+                    || !matches!(magic, Some(magic) if magic == MAGIC_NUMBER || magic.swap_bytes() == MAGIC_NUMBER)
+                {
+                    return Err(RendererError::ShaderCompile(ShaderError::new(
+                        label,
+                        String::from("not a SPIR-V binary"),
+                    )));
+                }
+                (
+                    wgpu::ShaderSource::SpirV(wgpu::util::make_spirv_raw(bytes)),
+                    None,
+                )
+            }
+            #[cfg(feature = "glsl")]
+            ShaderSource::Glsl { source, stage } => {
+                if stages != [*stage] {
+                    return Err(RendererError::ShaderCompile(ShaderError::new(
+                        label,
+                        format!(
+                            "a GLSL module holds only its {:?} stage, but {:?} are needed",
+                            stage, stages
+                        ),
+                    )));
+                }
+                let source = wgpu::ShaderSource::Glsl {
+                    shader: source.clone(),
+                    stage: *stage,
+                    defines: Default::default(),
+                };
+                (source, None)
+            }
+        };
+
+        let shader_module = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some(label),
+            source,
+        });
+        Ok((shader_module, module))
+    }
+}
+
+// Parses and validates WGSL with naga before it reaches the device, which
+// only reports errors through its error handler and without a structured
+// location
+fn validate_wgsl(source: &str, file: &str) -> Result<Module, RendererError> {
+    let error = |location: Option<SourceLocation>, message: String| {
+        RendererError::ShaderCompile(ShaderError {
+            file: file.to_string(),
+            line: location.map(|location| location.line_number),
+            column: location.map(|location| location.line_position),
+            message,
+        })
+    };
+
+    let module = wgsl::parse_str(source).map_err(|parse_error| {
+        error(
+            parse_error.location(source),
+            parse_error.message().to_string(),
+        )
+    })?;
+
+    // The device checks its own capabilities when the module is created
+    Validator::new(ValidationFlags::all(), Capabilities::all())
+        .validate(&module)
+        .map_err(|validation_error| {
+            let mut message = validation_error.as_inner().to_string();
+            let mut cause = std::error::Error::source(validation_error.as_inner());
+            while let Some(inner) = cause {
+                message = format!("{}: {}", message, inner);
+                cause = inner.source();
+            }
+            error(validation_error.location(source), message)
+        })?;
+    Ok(module)
+}
@@ -0,0 +1,58 @@
+// Bitmap font text drawn by DebugDraw. Each glyph is a quad offset in pixels
+// from an anchor that is either a world position or a pixel position.
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+    viewport_size: vec2<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var atlas: texture_2d<f32>;
+
+struct VertexInput {
+    @location(0) anchor: vec3<f32>,
+    // 1 for a world position anchor, 0 for a pixel position anchor
+    @location(1) world_space: f32,
+    @location(2) offset: vec2<f32>,
+    @location(3) texel: vec2<f32>,
+    @location(4) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) texel: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(in: VertexInput) -> VertexOutput {
+    var anchor: vec4<f32>;
+    if in.world_space > 0.5 {
+        anchor = camera.view_projection * vec4<f32>(in.anchor, 1.0);
+    } else {
+        let ndc = in.anchor.xy / camera.viewport_size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+        anchor = vec4<f32>(ndc, 0.0, 1.0);
+    }
+
+    // Offsets are in pixels with y pointing down, scaled by w so the text
+    // keeps its size after the perspective divide
+    let offset = in.offset / camera.viewport_size * vec2<f32>(2.0, -2.0) * anchor.w;
+
+    var out: VertexOutput;
+    out.position = vec4<f32>(anchor.xy + offset, anchor.zw);
+    out.texel = in.texel;
+    out.color = in.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureLoad(atlas, vec2<i32>(floor(in.texel)), 0).r;
+    if coverage < 0.5 {
+        discard;
+    }
+    return in.color;
+}
@@ -0,0 +1,84 @@
+// Built-in lit pipelines, a single directional light with Lambert diffuse
+// (fs_lambert) or Lambert diffuse plus Blinn-Phong specular (fs_blinn_phong)
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+    position: vec4<f32>,
+};
+
+struct Light {
+    // Direction the light travels in world space
+    direction: vec4<f32>,
+    color: vec4<f32>,
+    ambient: vec4<f32>,
+};
+
+struct Mesh {
+    model: mat4x4<f32>,
+    color: vec4<f32>,
+    // Specular color in rgb and shininess in w
+    specular: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+@group(0) @binding(1)
+var<uniform> light: Light;
+
+@group(1) @binding(0)
+var<uniform> mesh: Mesh;
+
+@group(2) @binding(0)
+var albedo: texture_2d<f32>;
+@group(2) @binding(1)
+var albedo_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+) -> VertexOutput {
+    let world_position = mesh.model * vec4<f32>(position, 1.0);
+
+    var out: VertexOutput;
+    out.position = camera.view_projection * world_position;
+    out.world_position = world_position.xyz;
+    // Only correct for uniformly scaled models, which avoids an inverse
+    // transpose per vertex
+    out.normal = (mesh.model * vec4<f32>(normal, 0.0)).xyz;
+    out.uv = uv;
+    return out;
+}
+
+fn diffuse(in: VertexOutput) -> vec4<f32> {
+    let base = textureSample(albedo, albedo_sampler, in.uv) * mesh.color;
+    let normal = normalize(in.normal);
+    let lambert = max(dot(normal, -normalize(light.direction.xyz)), 0.0);
+    return vec4<f32>(base.rgb * (light.ambient.rgb + light.color.rgb * lambert), base.a);
+}
+
+@fragment
+fn fs_lambert(in: VertexOutput) -> @location(0) vec4<f32> {
+    return diffuse(in);
+}
+
+@fragment
+fn fs_blinn_phong(in: VertexOutput) -> @location(0) vec4<f32> {
+    let normal = normalize(in.normal);
+    let to_light = -normalize(light.direction.xyz);
+    let to_camera = normalize(camera.position.xyz - in.world_position);
+    let half_vector = normalize(to_light + to_camera);
+    let facing = select(0.0, 1.0, dot(normal, to_light) > 0.0);
+    let specular = pow(max(dot(normal, half_vector), 0.0), max(mesh.specular.w, 1.0)) * facing;
+
+    let color = diffuse(in);
+    return vec4<f32>(color.rgb + mesh.specular.rgb * light.color.rgb * specular, color.a);
+}
@@ -0,0 +1,41 @@
+// Built-in unlit pipeline, the first texture of the material tinted by the
+// mesh color
+
+struct Camera {
+    view_projection: mat4x4<f32>,
+    position: vec4<f32>,
+};
+
+struct Mesh {
+    model: mat4x4<f32>,
+    color: vec4<f32>,
+};
+
+@group(0) @binding(0)
+var<uniform> camera: Camera;
+
+@group(1) @binding(0)
+var<uniform> mesh: Mesh;
+
+@group(2) @binding(0)
+var albedo: texture_2d<f32>;
+@group(2) @binding(1)
+var albedo_sampler: sampler;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@location(0) position: vec3<f32>, @location(1) uv: vec2<f32>) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = camera.view_projection * mesh.model * vec4<f32>(position, 1.0);
+    out.uv = uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(albedo, albedo_sampler, in.uv) * mesh.color;
+}
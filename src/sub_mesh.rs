@@ -1,4 +1,4 @@
-use crate::material_cache::MaterialCache;
+use crate::{error::RendererError, handle::MaterialHandle, material_cache::MaterialCache};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
     Buffer, BufferUsages, Device, IndexFormat, RenderPass,
@@ -9,7 +9,7 @@ pub struct SubMeshSource {
     pub indices: Vec<u8>,
     pub index_count: u32,
     pub index_type: IndexFormat, // Creates a codependency on wgpu
-    pub material_id: u64,
+    pub material: MaterialHandle,
 }
 
 pub struct SubMesh {
@@ -17,19 +17,19 @@ pub struct SubMesh {
     indices: Buffer,
     index_count: u32,
     index_type: IndexFormat,
-    material_id: u64,
+    material: MaterialHandle,
 }
 
 impl SubMesh {
-    pub fn from_source(device: &Device, source: &SubMeshSource) -> SubMesh {
+    pub fn from_source(device: &Device, label: &str, source: &SubMeshSource) -> SubMesh {
         let vertices = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
+            label: Some(&format!("{}/vertices", label)),
             contents: &source.vertices,
             usage: BufferUsages::VERTEX,
         });
 
         let indices = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
+            label: Some(&format!("{}/indices", label)),
             contents: &source.indices,
             usage: BufferUsages::INDEX,
         });
@@ -39,20 +39,27 @@ impl SubMesh {
             indices,
             index_count: source.index_count,
             index_type: source.index_type,
-            material_id: source.material_id,
+            material: source.material,
         }
     }
 
+    pub fn material(&self) -> &MaterialHandle {
+        &self.material
+    }
+
     pub fn record_commands<'a>(
         &'a self,
         render_pass: &mut RenderPass<'a>,
         first_available_bind_group: u32,
         material_cache: &'a MaterialCache,
-    ) {
-        let material = material_cache.get(&self.material_id).unwrap();
+    ) -> Result<(), RendererError> {
+        let material = material_cache
+            .get(&self.material)
+            .ok_or(RendererError::UnknownMaterial)?;
         render_pass.set_bind_group(first_available_bind_group, material.bind_group(), &[]);
         render_pass.set_vertex_buffer(0, self.vertices.slice(..));
         render_pass.set_index_buffer(self.indices.slice(..), self.index_type);
         render_pass.draw_indexed(0..self.index_count, 0, 0..1);
+        Ok(())
     }
 }
@@ -0,0 +1,167 @@
+use crate::frame_encoder::FrameStatistics;
+use std::fmt::Debug;
+
+// Decides when queued draws are recorded into render passes and when recorded
+// passes are submitted to the GPU. Submitting early keeps the GPU busy while
+// the frame is still being built, but every submission has a fixed cost that
+// is especially high on tile based and software GPUs.
+pub trait SubmissionPolicy: Debug + Send {
+    // Whether a pipeline with this many queued draws should record them now
+    fn should_record(&self, queued_draws: usize) -> bool;
+
+    // Whether the passes recorded since the last submission should be
+    // submitted now. Whatever is left is submitted when the frame ends.
+    fn should_submit(&self, pending_passes: u32) -> bool;
+
+    // Called with the statistics of each frame once it has been submitted
+    fn end_frame(&mut self, _statistics: &FrameStatistics) {}
+
+    fn clone_box(&self) -> Box<dyn SubmissionPolicy>;
+}
+
+impl Clone for Box<dyn SubmissionPolicy> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+// Records and submits every time a pipeline has queued a fixed number of draws
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FixedCount {
+    pub draws_per_submission: usize,
+}
+
+impl Default for FixedCount {
+    fn default() -> Self {
+        Self {
+            draws_per_submission: 5,
+        }
+    }
+}
+
+impl SubmissionPolicy for FixedCount {
+    fn should_record(&self, queued_draws: usize) -> bool {
+        queued_draws >= self.draws_per_submission
+    }
+
+    fn should_submit(&self, _pending_passes: u32) -> bool {
+        true
+    }
+
+    fn clone_box(&self) -> Box<dyn SubmissionPolicy> {
+        Box::new(*self)
+    }
+}
+
+// Tunes the number of draws per submission at the end of every frame so that
+// frames converge on a target number of submissions
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct AdaptiveTarget {
+    pub target_submissions: u32,
+    pub draws_per_submission: usize,
+    pub max_draws_per_submission: usize,
+}
+
+impl AdaptiveTarget {
+    pub fn new(target_submissions: u32) -> Self {
+        Self {
+            target_submissions: target_submissions.max(1),
+            draws_per_submission: FixedCount::default().draws_per_submission,
+            max_draws_per_submission: 4096,
+        }
+    }
+}
+
+impl SubmissionPolicy for AdaptiveTarget {
+    fn should_record(&self, queued_draws: usize) -> bool {
+        queued_draws >= self.draws_per_submission
+    }
+
+    fn should_submit(&self, _pending_passes: u32) -> bool {
+        true
+    }
+
+    fn end_frame(&mut self, statistics: &FrameStatistics) {
+        if statistics.draws == 0 || statistics.submissions == self.target_submissions {
+            return;
+        }
+
+        // Move halfway towards the batch size that would have hit the target
+        // last frame so a single unusual frame does not swing it too far
+        let ideal = (statistics.draws as usize).div_ceil(self.target_submissions as usize);
+        self.draws_per_submission = (self.draws_per_submission + ideal)
+            .div_ceil(2)
+            .clamp(1, self.max_draws_per_submission);
+    }
+
+    fn clone_box(&self) -> Box<dyn SubmissionPolicy> {
+        Box::new(*self)
+    }
+}
+
+// Records one pass per pipeline into a single encoder submitted at the end of
+// the frame
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+pub struct OncePerFrame;
+
+impl SubmissionPolicy for OncePerFrame {
+    fn should_record(&self, _queued_draws: usize) -> bool {
+        false
+    }
+
+    fn should_submit(&self, _pending_passes: u32) -> bool {
+        false
+    }
+
+    fn clone_box(&self) -> Box<dyn SubmissionPolicy> {
+        Box::new(*self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs frames of a fixed number of draws, submitting whenever a batch is
+    // full like a single pipeline would, and returns the submissions of each
+    fn simulate(policy: &mut AdaptiveTarget, draws: u32, frames: usize) -> Vec<u32> {
+        (0..frames)
+            .map(|_| {
+                let submissions = draws.div_ceil(policy.draws_per_submission as u32);
+                policy.end_frame(&FrameStatistics {
+                    submissions,
+                    render_passes: submissions,
+                    draws,
+                });
+                submissions
+            })
+            .collect()
+    }
+
+    #[test]
+    fn adaptive_target_converges() {
+        let mut policy = AdaptiveTarget::new(2);
+        assert_eq!(simulate(&mut policy, 40, 6), vec![8, 4, 3, 3, 2, 2]);
+        assert_eq!(policy.draws_per_submission, 20);
+    }
+
+    #[test]
+    fn adaptive_target_ignores_empty_frames() {
+        let mut policy = AdaptiveTarget::new(2);
+        policy.end_frame(&FrameStatistics::default());
+        assert_eq!(policy, AdaptiveTarget::new(2));
+    }
+
+    #[test]
+    fn adaptive_target_is_clamped() {
+        let mut policy = AdaptiveTarget {
+            max_draws_per_submission: 8,
+            ..AdaptiveTarget::new(1)
+        };
+        simulate(&mut policy, 1000, 4);
+        assert_eq!(policy.draws_per_submission, 8);
+
+        // A target of zero submissions is raised to one
+        assert_eq!(AdaptiveTarget::new(0).target_submissions, 1);
+    }
+}
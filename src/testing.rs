@@ -0,0 +1,132 @@
+// Helpers for golden image regression tests. Frames are rendered with a
+// headless renderer, captured and compared against reference PNGs stored
+// alongside the tests.
+
+use crate::{
+    MeshHandle, PipelineHandle, Renderer, RendererConfiguration, RendererConfigurationBuilder,
+    RendererError,
+};
+use derive_builder::Builder;
+use image::RgbaImage;
+use std::path::PathBuf;
+use wgpu::{Backends, InstanceFlags};
+
+pub use crate::image_comparison::{compare_images, ImageComparison};
+
+// Setting this environment variable to 1 writes the captured frames as the
+// stored references instead of comparing against them. It is the only way to
+// create a reference, a missing one fails the check so that a renamed or
+// deleted reference can not pass unnoticed.
+pub const BLESS_ENVIRONMENT_VARIABLE: &str = "RENDERER2_BLESS";
+
+fn blessing() -> bool {
+    std::env::var_os(BLESS_ENVIRONMENT_VARIABLE).is_some_and(|value| value == "1")
+}
+
+pub fn headless_renderer(width: u32, height: u32) -> Result<Renderer<'static>, RendererError> {
+    Renderer::new_headless(&headless_configuration(width, height)?)
+}
+
+pub fn headless_configuration(
+    width: u32,
+    height: u32,
+) -> Result<RendererConfiguration, RendererError> {
+    Ok(RendererConfigurationBuilder::default()
+        .instance_flags(InstanceFlags::default())
+        .window_width(width)
+        .window_height(height)
+        // CI machines usually only have lavapipe (Vulkan) or llvmpipe (GL)
+        .backends(wgpu::util::backend_bits_from_env().unwrap_or(Backends::VULKAN | Backends::GL))
+        .build()?)
+}
+
+// Number of distinct bind group layouts the renderer has created
+pub fn bind_group_layout_count(renderer: &Renderer) -> usize {
+    renderer.bind_group_layout_count()
+}
+
+// Queues the mesh on the pipeline without validating either, so that tests can
+// make recording fail
+pub fn queue_unchecked(renderer: &mut Renderer, pipeline: PipelineHandle, mesh: MeshHandle) {
+    renderer.queue_unchecked(pipeline, mesh);
+}
+
+#[derive(Debug, Clone, Builder)]
+pub struct GoldenImage {
+    pub name: String,
+    #[builder(default = "PathBuf::from(\"tests/golden\")")]
+    pub reference_directory: PathBuf,
+    #[builder(default = "PathBuf::from(\"target/golden\")")]
+    pub output_directory: PathBuf,
+    // Largest difference allowed on any single channel of any pixel
+    #[builder(default = "2")]
+    pub tolerance: u8,
+}
+
+impl GoldenImage {
+    pub fn reference_path(&self) -> PathBuf {
+        self.reference_directory.join(format!("{}.png", self.name))
+    }
+
+    // Compares the frame against the stored reference, or writes the frame as
+    // the reference when blessing. On mismatch the frame and a diff image are
+    // written to the output directory.
+    pub fn check(&self, frame: &RgbaImage) -> Result<(), String> {
+        let reference_path = self.reference_path();
+        if blessing() {
+            std::fs::create_dir_all(&self.reference_directory).map_err(|e| e.to_string())?;
+            frame.save(&reference_path).map_err(|e| e.to_string())?;
+            log::warn!("Wrote golden image reference {}", reference_path.display());
+            return Ok(());
+        }
+        if !reference_path.exists() {
+            return Err(format!(
+                "{}: no reference at {}, run with {}=1 to create it",
+                self.name,
+                reference_path.display(),
+                BLESS_ENVIRONMENT_VARIABLE
+            ));
+        }
+
+        let reference = image::open(&reference_path)
+            .map_err(|e| format!("{}: {}", reference_path.display(), e))?
+            .to_rgba8();
+
+        if reference.dimensions() != frame.dimensions() {
+            return Err(format!(
+                "{}: expected a {:?} image but the frame is {:?}",
+                self.name,
+                reference.dimensions(),
+                frame.dimensions()
+            ));
+        }
+
+        let comparison = compare_images(frame, &reference, self.tolerance);
+        if comparison.matches() {
+            return Ok(());
+        }
+
+        std::fs::create_dir_all(&self.output_directory).map_err(|e| e.to_string())?;
+        let actual_path = self
+            .output_directory
+            .join(format!("{}.actual.png", self.name));
+        let diff_path = self
+            .output_directory
+            .join(format!("{}.diff.png", self.name));
+        frame.save(&actual_path).map_err(|e| e.to_string())?;
+        comparison
+            .diff
+            .save(&diff_path)
+            .map_err(|e| e.to_string())?;
+
+        Err(format!(
+            "{}: {} pixels differ by more than {} (max difference {}), see {} and {}",
+            self.name,
+            comparison.mismatched_pixels,
+            self.tolerance,
+            comparison.max_channel_difference,
+            actual_path.display(),
+            diff_path.display()
+        ))
+    }
+}
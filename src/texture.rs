@@ -1,3 +1,4 @@
+use crate::error::RendererError;
 use wgpu::{
     AddressMode, CompareFunction, Device, Extent3d, FilterMode, Queue, Sampler, SamplerDescriptor,
     TextureDescriptor, TextureDimension, TextureFormat, TextureView, TextureViewDescriptor,
@@ -11,12 +12,16 @@ pub struct TextureSource {
 }
 
 pub struct Texture {
-    _texture: wgpu::Texture,
+    texture: wgpu::Texture,
     view: TextureView,
     sampler: Sampler,
 }
 
 impl Texture {
+    pub fn texture(&self) -> &wgpu::Texture {
+        &self.texture
+    }
+
     pub fn view(&self) -> &TextureView {
         &self.view
     }
@@ -66,19 +71,74 @@ impl Texture {
         });
 
         Self {
-            _texture: texture,
+            texture,
+            view,
+            sampler,
+        }
+    }
+
+    pub fn new_render_target(
+        device: &Device,
+        width: u32,
+        height: u32,
+        label: &str,
+        format: &TextureFormat,
+    ) -> Self {
+        let size = Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some(label),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: *format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                | wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+
+        let view = texture.create_view(&TextureViewDescriptor::default());
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some(label),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Nearest,
+            mipmap_filter: FilterMode::Nearest,
+            lod_min_clamp: 0.0,
+            lod_max_clamp: 100.0,
+            compare: None,
+            ..Default::default()
+        });
+
+        Self {
+            texture,
             view,
             sampler,
         }
     }
 
-    pub fn from_source(device: &Device, queue: &Queue, binary_texture: &TextureSource) -> Self {
+    pub fn from_source(
+        device: &Device,
+        queue: &Queue,
+        binary_texture: &TextureSource,
+    ) -> Result<Self, RendererError> {
         let size = Extent3d {
             width: binary_texture.width,
             height: binary_texture.height,
             depth_or_array_layers: 1,
         };
 
+        let bytes_per_row = Texture::bytes_per_row(binary_texture.width, &binary_texture.format)?;
+
         let texture = device.create_texture(&TextureDescriptor {
             label: None,
             size,
@@ -100,10 +160,7 @@ impl Texture {
             &binary_texture.data,
             wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: Some(Texture::bytes_per_row(
-                    binary_texture.width,
-                    &binary_texture.format,
-                )),
+                bytes_per_row: Some(bytes_per_row),
                 rows_per_image: Some(binary_texture.height),
             },
             size,
@@ -125,14 +182,22 @@ impl Texture {
             ..Default::default()
         });
 
-        Self {
-            _texture: texture,
+        Ok(Self {
+            texture,
             view,
             sampler,
-        }
+        })
+    }
+
+    // Buffer copies require each row to start on a COPY_BYTES_PER_ROW_ALIGNMENT
+    // boundary, so rows read back from the GPU carry trailing padding
+    pub fn padded_bytes_per_row(width: u32, format: &TextureFormat) -> Result<u32, RendererError> {
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let unpadded = Texture::bytes_per_row(width, format)?;
+        Ok(unpadded.div_ceil(alignment) * alignment)
     }
 
-    fn bytes_per_row(width: u32, format: &TextureFormat) -> u32 {
+    pub fn bytes_per_row(width: u32, format: &TextureFormat) -> Result<u32, RendererError> {
         let bytes_per_pixel = match format {
             TextureFormat::R8Unorm => 1,
             TextureFormat::R8Snorm => 1,
@@ -183,12 +248,9 @@ impl Texture {
             TextureFormat::Depth24PlusStencil8 => 4,
             TextureFormat::Depth32Float => 4,
             TextureFormat::Depth32FloatStencil8 => 5,
-            format => panic!(
-                "Please fill this table with the bytes per pixel for format: {:?}",
-                format
-            ),
+            format => return Err(RendererError::UnsupportedTextureFormat(*format)),
         };
 
-        width * bytes_per_pixel
+        Ok(width * bytes_per_pixel)
     }
 }
@@ -0,0 +1,379 @@
+use crate::uniform::UniformSource;
+use bytemuck::Pod;
+use glam::{IVec2, IVec3, IVec4, Mat2, Mat4, UVec2, UVec3, UVec4, Vec2, Vec3, Vec4};
+use std::mem::size_of;
+use wgpu::BufferBindingType;
+
+// A type with a known layout in WGSL. Rust types only match the WGSL layout
+// when their size equals SIZE, which the wgsl_struct macro and TypedUniform
+// check at compile time.
+pub trait WgslType: Pod {
+    // AlignOf and SizeOf of the type in WGSL
+    const ALIGNMENT: usize;
+    const SIZE: usize;
+    // Structures and arrays, which uniform buffers align to 16 bytes
+    const COMPOSITE: bool = false;
+    // Whether the type satisfies the additional layout rules of the uniform
+    // address space (the std140 like rules) without alignment attributes.
+    // Storage buffers only need the plain layout (the std430 like rules).
+    const UNIFORM_COMPATIBLE: bool = true;
+}
+
+macro_rules! impl_wgsl_type {
+    ($alignment:literal, $size:literal, $($ty:ty),*) => {
+        $(
+            impl WgslType for $ty {
+                const ALIGNMENT: usize = $alignment;
+                const SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+impl_wgsl_type!(4, 4, f32, i32, u32);
+impl_wgsl_type!(8, 8, Vec2, IVec2, UVec2);
+impl_wgsl_type!(16, 12, Vec3, IVec3, UVec3);
+impl_wgsl_type!(16, 16, Vec4, IVec4, UVec4);
+impl_wgsl_type!(8, 16, Mat2);
+impl_wgsl_type!(16, 64, Mat4);
+// glam's Mat3 is 36 bytes while mat3x3 has 16 byte columns, pad the columns
+// to Vec4 and declare the uniform as mat3x4 or three vec4 instead
+
+impl<T: WgslType, const N: usize> WgslType for [T; N]
+where
+    [T; N]: Pod,
+{
+    const ALIGNMENT: usize = T::ALIGNMENT;
+    const SIZE: usize = N * round_up(T::ALIGNMENT, T::SIZE);
+    const COMPOSITE: bool = true;
+    const UNIFORM_COMPATIBLE: bool =
+        T::UNIFORM_COMPATIBLE && round_up(T::ALIGNMENT, T::SIZE) % 16 == 0;
+}
+
+// Declares a repr(C) structure implementing Pod and WgslType. Compilation
+// fails when a field is not at the offset WGSL gives it or the structure
+// would contain padding, the classic case being a vec3 followed by a vec4,
+// which needs an explicit padding field in between:
+//
+// wgsl_struct! {
+//     pub struct Light {
+//         pub direction: Vec3,
+//         pub _padding: f32,
+//         pub color: Vec4,
+//     }
+// }
+#[macro_export]
+macro_rules! wgsl_struct {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $($field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        #[repr(C)]
+        #[derive(Copy, Clone)]
+        $vis struct $name {
+            $($field_vis $field: $ty),*
+        }
+
+        // Sound since every field is Pod and the checks below rule out padding
+        unsafe impl $crate::bytemuck::Zeroable for $name {}
+        unsafe impl $crate::bytemuck::Pod for $name {}
+
+        impl $crate::WgslType for $name {
+            const ALIGNMENT: usize = $crate::WgslField::alignment(&[
+                $($crate::WgslField::of::<$ty>(stringify!($field), 0)),*
+            ]);
+            const SIZE: usize = $crate::WgslField::size(&[
+                $($crate::WgslField::of::<$ty>(stringify!($field), 0)),*
+            ]);
+            const COMPOSITE: bool = true;
+            const UNIFORM_COMPATIBLE: bool = $crate::WgslField::uniform_compatible(&[
+                $($crate::WgslField::of::<$ty>(
+                    stringify!($field),
+                    ::std::mem::offset_of!($name, $field),
+                )),*
+            ]);
+        }
+
+        const _: () = {
+            let fields = [
+                $($crate::WgslField::of::<$ty>(stringify!($field), 0)),*
+            ];
+            $(
+                assert!(
+                    ::std::mem::size_of::<$ty>() == <$ty as $crate::WgslType>::SIZE,
+                    concat!(
+                        "the type of ", stringify!($name), "::", stringify!($field),
+                        " has a different size in WGSL"
+                    ),
+                );
+                assert!(
+                    ::std::mem::offset_of!($name, $field)
+                        == $crate::WgslField::offset(&fields, stringify!($field)),
+                    concat!(
+                        stringify!($name), "::", stringify!($field),
+                        " is not at its WGSL offset, add padding before it"
+                    ),
+                );
+            )*
+            assert!(
+                ::std::mem::size_of::<$name>() == <$name as $crate::WgslType>::SIZE,
+                concat!(stringify!($name), " is not padded to its WGSL size"),
+            );
+            assert!(
+                ::std::mem::size_of::<$name>() == 0 $(+ ::std::mem::size_of::<$ty>())*,
+                concat!(stringify!($name), " contains implicit padding"),
+            );
+        };
+    };
+}
+
+// The layout of a field declared by wgsl_struct. Offsets are only filled in
+// when checking the uniform rules, the other layouts are computed from the
+// alignment and size of the fields alone.
+#[derive(Debug, Copy, Clone)]
+pub struct WgslField {
+    name: &'static str,
+    offset: usize,
+    alignment: usize,
+    size: usize,
+    composite: bool,
+    uniform_compatible: bool,
+}
+
+impl WgslField {
+    pub const fn of<T: WgslType>(name: &'static str, offset: usize) -> Self {
+        Self {
+            name,
+            offset,
+            alignment: T::ALIGNMENT,
+            size: T::SIZE,
+            composite: T::COMPOSITE,
+            uniform_compatible: T::UNIFORM_COMPATIBLE,
+        }
+    }
+
+    pub const fn alignment(fields: &[WgslField]) -> usize {
+        let mut alignment = 1;
+        let mut i = 0;
+        while i < fields.len() {
+            if fields[i].alignment > alignment {
+                alignment = fields[i].alignment;
+            }
+            i += 1;
+        }
+        alignment
+    }
+
+    pub const fn size(fields: &[WgslField]) -> usize {
+        let mut end = 0;
+        let mut i = 0;
+        while i < fields.len() {
+            end = round_up(fields[i].alignment, end) + fields[i].size;
+            i += 1;
+        }
+        round_up(Self::alignment(fields), end)
+    }
+
+    // The WGSL offset of the named field
+    pub const fn offset(fields: &[WgslField], name: &str) -> usize {
+        let mut offset = 0;
+        let mut i = 0;
+        while i < fields.len() {
+            offset = round_up(fields[i].alignment, offset);
+            if str_eq(fields[i].name, name) {
+                return offset;
+            }
+            offset += fields[i].size;
+            i += 1;
+        }
+        panic!("no such field")
+    }
+
+    // Structures and arrays in uniform buffers must start at a multiple of 16
+    // bytes, and whatever follows a structure must start 16 byte aligned
+    pub const fn uniform_compatible(fields: &[WgslField]) -> bool {
+        let mut i = 0;
+        while i < fields.len() {
+            let field = &fields[i];
+            if !field.uniform_compatible {
+                return false;
+            }
+            if field.composite {
+                if field.offset % round_up(16, field.alignment) != 0 {
+                    return false;
+                }
+                if i + 1 < fields.len()
+                    && fields[i + 1].offset - field.offset < round_up(16, field.size)
+                {
+                    return false;
+                }
+            }
+            i += 1;
+        }
+        true
+    }
+}
+
+const fn round_up(alignment: usize, size: usize) -> usize {
+    size.div_ceil(alignment) * alignment
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+// A uniform holding a single value whose Rust layout matches its WGSL
+// layout in uniform buffers, which is checked when the uniform is created
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TypedUniform<T> {
+    value: T,
+}
+
+impl<T: WgslType> TypedUniform<T> {
+    pub fn new(value: T) -> Self {
+        const {
+            assert!(
+                size_of::<T>() == T::SIZE,
+                "the type has a different size in WGSL"
+            );
+            assert!(
+                T::UNIFORM_COMPATIBLE,
+                "the type breaks the layout rules of uniform buffers"
+            );
+        }
+        Self { value }
+    }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn set(&mut self, value: T) {
+        self.value = value;
+    }
+
+    // The contents to pass to Renderer::update_mesh_uniform and
+    // Renderer::update_pipeline_global, or pass the value to their typed forms
+    pub fn bytes(&self) -> &[u8] {
+        bytemuck::bytes_of(&self.value)
+    }
+
+    pub fn source(&self) -> UniformSource {
+        UniformSource {
+            data: self.bytes().to_vec(),
+            binding_type: BufferBindingType::Uniform,
+        }
+    }
+}
+
+impl<T: WgslType> From<&TypedUniform<T>> for UniformSource {
+    fn from(uniform: &TypedUniform<T>) -> Self {
+        uniform.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    crate::wgsl_struct! {
+        struct Camera {
+            view_projection: Mat4,
+            position: Vec4,
+        }
+    }
+
+    // A vec3 packs with a following scalar
+    crate::wgsl_struct! {
+        struct Light {
+            direction: Vec3,
+            intensity: f32,
+            color: Vec4,
+        }
+    }
+
+    crate::wgsl_struct! {
+        struct Lights {
+            ambient: Vec4,
+            lights: [Light; 2],
+        }
+    }
+
+    crate::wgsl_struct! {
+        struct Half {
+            value: Vec2,
+        }
+    }
+
+    // Uniform buffers require whatever follows a structure to start 16 bytes
+    // after it, the count directly follows the 8 byte structure
+    crate::wgsl_struct! {
+        struct Unpadded {
+            half: Half,
+            count: u32,
+            _padding: u32,
+        }
+    }
+
+    #[test]
+    fn structure_layouts() {
+        assert_eq!((Camera::ALIGNMENT, Camera::SIZE), (16, 80));
+        assert_eq!((Light::ALIGNMENT, Light::SIZE), (16, 32));
+        assert_eq!(Lights::SIZE, 80);
+        assert_eq!((Half::ALIGNMENT, Half::SIZE), (8, 8));
+        assert_eq!(Unpadded::SIZE, 16);
+        assert_eq!(
+            WgslField::offset(
+                &[WgslField::of::<f32>("a", 0), WgslField::of::<Vec3>("b", 0),],
+                "b"
+            ),
+            16
+        );
+    }
+
+    #[test]
+    fn array_layouts() {
+        // Elements are padded to their alignment
+        assert_eq!(<[Vec3; 2]>::SIZE, 32);
+        assert_eq!(<[f32; 4]>::SIZE, 16);
+        assert_eq!(<[Light; 2]>::SIZE, 64);
+    }
+
+    #[test]
+    fn uniform_rules() {
+        const {
+            assert!(Camera::UNIFORM_COMPATIBLE);
+            assert!(Lights::UNIFORM_COMPATIBLE);
+            assert!(<[Vec4; 4]>::UNIFORM_COMPATIBLE);
+            // Arrays in uniform buffers need a 16 byte stride
+            assert!(!<[f32; 4]>::UNIFORM_COMPATIBLE);
+            assert!(!Unpadded::UNIFORM_COMPATIBLE);
+        }
+    }
+
+    #[test]
+    fn typed_uniform_bytes() {
+        let mut uniform = TypedUniform::new(Vec4::new(1.0, 2.0, 3.0, 4.0));
+        uniform.set(Vec4::ONE);
+        assert_eq!(uniform.value(), &Vec4::ONE);
+        assert_eq!(uniform.bytes(), bytemuck::bytes_of(&[1.0f32; 4]));
+
+        let source = UniformSource::from(&uniform);
+        assert_eq!(source.data.len(), 16);
+        assert_eq!(source.binding_type, BufferBindingType::Uniform);
+    }
+}
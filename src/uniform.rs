@@ -1,38 +1,78 @@
+use crate::{error::RendererError, frame_encoder::FrameEncoder};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    Buffer, BufferUsages, Device,
+    Buffer, BufferBindingType, BufferUsages, Device, Queue,
 };
 
 pub struct UniformSource {
     pub data: Vec<u8>,
+    // Uniform, or storage for data beyond the uniform buffer limits such as
+    // light lists, skinning palettes or per-instance arrays
+    pub binding_type: BufferBindingType,
 }
 
 pub struct Uniform {
-    staging_buffer: Buffer,
     buffer: Buffer,
+    // The length of the source data, the buffer is padded to the copy alignment
+    size: usize,
 }
 
 impl Uniform {
-    pub fn from_source(binary: &UniformSource, device: &Device) -> Self {
-        let staging_buffer = device.create_buffer_init(&BufferInitDescriptor {
-            label: None,
-            contents: &binary.data,
-            usage: BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
-        });
+    pub fn from_source(binary: &UniformSource, device: &Device) -> Result<Self, RendererError> {
+        let limits = device.limits();
+        let (limit, usage) = match binary.binding_type {
+            BufferBindingType::Uniform => (
+                limits.max_uniform_buffer_binding_size,
+                BufferUsages::UNIFORM,
+            ),
+            BufferBindingType::Storage { .. } => (
+                limits.max_storage_buffer_binding_size,
+                BufferUsages::STORAGE,
+            ),
+        };
+        if binary.data.len() as u64 > limit as u64 {
+            return Err(RendererError::UniformTooLarge {
+                size: binary.data.len(),
+                limit,
+            });
+        }
 
         let buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: None,
             contents: &binary.data,
-            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+            usage: BufferUsages::COPY_DST | usage,
         });
 
-        Self {
-            staging_buffer,
+        Ok(Self {
             buffer,
-        }
+            size: binary.data.len(),
+        })
     }
 
     pub fn buffer(&self) -> &Buffer {
         &self.buffer
     }
+
+    // Replaces the contents of the buffer through the frame encoder, see
+    // FrameEncoder::write_buffer for the ordering. The data has to be as long
+    // as the source the uniform was created from.
+    pub fn write(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        frame_encoder: &mut FrameEncoder,
+        data: &[u8],
+    ) -> Result<(), RendererError> {
+        if data.len() != self.size {
+            return Err(RendererError::UniformSize {
+                expected: self.size,
+                actual: data.len(),
+            });
+        }
+
+        let mut padded = data.to_vec();
+        padded.resize(self.buffer.size() as usize, 0);
+        frame_encoder.write_buffer(device, queue, &self.buffer, &padded);
+        Ok(())
+    }
 }
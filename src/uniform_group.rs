@@ -1,8 +1,14 @@
-use crate::uniform::{Uniform, UniformSource};
+use crate::{
+    bind_group_layout_cache::BindGroupLayoutCache,
+    error::RendererError,
+    frame_encoder::FrameEncoder,
+    reflection,
+    uniform::{Uniform, UniformSource},
+};
+use std::num::NonZeroU64;
 use wgpu::{
-    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingResource, BindingType, BufferBinding, BufferBindingType, Device,
-    ShaderStages,
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutEntry, BindingResource,
+    BindingType, BufferBinding, BufferBindingType, Device, Queue, ShaderStages,
 };
 
 pub struct UniformGroupSource {
@@ -10,41 +16,50 @@ pub struct UniformGroupSource {
     pub uniform_sources: Vec<UniformSource>,
 }
 
+impl UniformGroupSource {
+    // The layout of the group with the size of each uniform as its minimum
+    // binding size, used to check the group against a shader
+    pub(crate) fn sized_layout_entries(&self) -> Vec<BindGroupLayoutEntry> {
+        self.uniform_sources
+            .iter()
+            .enumerate()
+            .map(|(binding, uniform_source)| {
+                buffer_layout_entry(
+                    binding as u32,
+                    uniform_source.binding_type,
+                    NonZeroU64::new(uniform_source.data.len() as u64),
+                )
+            })
+            .collect()
+    }
+}
+
 pub struct UniformGroup {
-    name: String,
     uniforms: Vec<Uniform>,
     bind_group: BindGroup,
+    // The sized layout the group was checked with
+    layout_entries: Vec<BindGroupLayoutEntry>,
 }
 
 impl UniformGroup {
-    pub fn from_source(source: &UniformGroupSource, device: &Device) -> Self {
+    // The group is created with the layout entries, without their sizes, so
+    // that it takes the visibility the pipeline configures for its bindings
+    pub fn from_source(
+        source: &UniformGroupSource,
+        layout_entries: &[BindGroupLayoutEntry],
+        device: &Device,
+        layouts: &mut BindGroupLayoutCache,
+    ) -> Result<Self, RendererError> {
         let mut uniforms = Vec::new();
-        let mut layout_entries = Vec::new();
-        let mut binding_index = 0;
         for uniform_source in &source.uniform_sources {
-            let uniform = Uniform::from_source(uniform_source, device);
-
-            let uniform_layout_entry = BindGroupLayoutEntry {
-                binding: binding_index,
-                visibility: ShaderStages::VERTEX_FRAGMENT,
-                ty: BindingType::Buffer {
-                    ty: BufferBindingType::Uniform,
-                    has_dynamic_offset: false,
-                    min_binding_size: None,
-                },
-                count: None,
-            };
-
-            binding_index += 1;
+            let uniform = Uniform::from_source(uniform_source, device)?;
             uniforms.push(uniform);
-            layout_entries.push(uniform_layout_entry);
         }
 
-        binding_index = 0;
         let mut entries = Vec::new();
-        for uniform in &uniforms {
+        for (binding_index, uniform) in uniforms.iter().enumerate() {
             let uniform_entry = BindGroupEntry {
-                binding: binding_index,
+                binding: binding_index as u32,
                 resource: BindingResource::Buffer(BufferBinding {
                     buffer: uniform.buffer(),
                     offset: 0,
@@ -52,28 +67,70 @@ impl UniformGroup {
                 }),
             };
             entries.push(uniform_entry);
-            binding_index += 1;
         }
 
-        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: Some(&source.name),
-            entries: &layout_entries,
-        });
-
+        let layout = layouts.get_or_create(device, &reflection::without_sizes(layout_entries));
         let bind_group = device.create_bind_group(&BindGroupDescriptor {
             label: Some(&source.name),
             layout: &layout,
             entries: &entries,
         });
 
-        Self {
-            name: source.name.clone(),
+        Ok(Self {
             uniforms,
             bind_group,
-        }
+            layout_entries: layout_entries.to_vec(),
+        })
+    }
+
+    pub fn layout_entries(&self) -> &[BindGroupLayoutEntry] {
+        &self.layout_entries
     }
 
     pub fn bind_group(&self) -> &BindGroup {
         &self.bind_group
     }
+
+    // Replaces the contents of the uniform at the binding index, see
+    // Uniform::write for the ordering
+    pub fn write_uniform(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        frame_encoder: &mut FrameEncoder,
+        index: usize,
+        data: &[u8],
+    ) -> Result<(), RendererError> {
+        self.uniforms
+            .get(index)
+            .ok_or(RendererError::UnknownUniform)?
+            .write(device, queue, frame_encoder, data)
+    }
+}
+
+fn buffer_layout_entry(
+    binding: u32,
+    ty: BufferBindingType,
+    min_binding_size: Option<NonZeroU64>,
+) -> BindGroupLayoutEntry {
+    BindGroupLayoutEntry {
+        binding,
+        visibility: buffer_visibility(ty),
+        ty: BindingType::Buffer {
+            ty,
+            has_dynamic_offset: false,
+            min_binding_size,
+        },
+        count: None,
+    }
+}
+
+// Buffers are visible to both stages unless the pipeline configures otherwise,
+// except read-write storage buffers which the vertex stage can not bind
+// without the VERTEX_WRITABLE_STORAGE feature
+pub(crate) fn buffer_visibility(ty: BufferBindingType) -> ShaderStages {
+    match ty {
+        BufferBindingType::Storage { read_only: false } => ShaderStages::FRAGMENT,
+        _ => ShaderStages::VERTEX_FRAGMENT,
+    }
 }
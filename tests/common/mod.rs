@@ -0,0 +1,241 @@
+// Scene fixtures shared by the integration tests and the benchmarks. Each
+// test binary only uses some of them.
+#![allow(dead_code)]
+
+use glam::Mat4;
+use image::RgbaImage;
+use renderer2::{
+    testing::{headless_renderer, GoldenImageBuilder},
+    MaterialHandle, MaterialSource, MeshHandle, MeshSource, PipelineConfiguration, PipelineHandle,
+    Renderer, ShaderSource, SubMeshSource, TextureSource, UniformGroupSource, UniformSource,
+    VertexBufferLayoutSource,
+};
+use std::path::PathBuf;
+use wgpu::{
+    BindGroupLayoutEntry, BindingType, BufferBindingType, SamplerBindingType, ShaderStages,
+    TextureSampleType, TextureViewDimension, VertexAttribute, VertexFormat, VertexStepMode,
+};
+
+const VERTEX_ATTRIBUTES: [VertexAttribute; 2] = [
+    VertexAttribute {
+        format: VertexFormat::Float32x3,
+        offset: 0,
+        shader_location: 0,
+    },
+    VertexAttribute {
+        format: VertexFormat::Float32x2,
+        offset: 12,
+        shader_location: 1,
+    },
+];
+
+pub const WIDTH: u32 = 64;
+pub const HEIGHT: u32 = 64;
+
+// A shader in tests/shaders, independent of the working directory
+pub fn shader_path(name: &str) -> PathBuf {
+    PathBuf::from(format!(
+        "{}/tests/shaders/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    ))
+}
+
+pub fn shader_source(name: &str) -> ShaderSource {
+    ShaderSource::Path(shader_path(name))
+}
+
+pub fn check_golden(name: &str, image: &RgbaImage) {
+    GoldenImageBuilder::default()
+        .name(String::from(name))
+        .build()
+        .unwrap()
+        .check(image)
+        .unwrap();
+}
+
+pub fn textured_pipeline() -> PipelineConfiguration {
+    PipelineConfiguration {
+        shader_source: shader_source("textured.wgsl"),
+        vertex_shader_entrypoint: String::from("vs_main"),
+        vertex_buffer_layouts: vec![VertexBufferLayoutSource {
+            array_stride: 20,
+            step_mode: VertexStepMode::Vertex,
+            attributes: VERTEX_ATTRIBUTES.to_vec(),
+        }],
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        strip_index_format: None,
+        front_face: wgpu::FrontFace::Ccw,
+        cull_mode: None,
+        polygon_mode: wgpu::PolygonMode::Fill,
+        depth_write_enabled: true,
+        depth_compare_function: wgpu::CompareFunction::Less,
+        fragment_shader_source: None,
+        fragment_shader_entrypoint: String::from("fs_main"),
+        fragment_shader_blend_mode: Some(wgpu::BlendState::REPLACE),
+        fragment_shader_write_mask: wgpu::ColorWrites::ALL,
+        bind_group_layouts: vec![
+            // Only the fragment stage reads the tint
+            vec![BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            vec![
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::VERTEX_FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        ],
+    }
+}
+
+pub fn checkerboard_material() -> MaterialSource {
+    let white = [255, 255, 255, 255];
+    let black = [0, 0, 0, 255];
+    MaterialSource {
+        texture_sources: vec![TextureSource {
+            data: [white, black, black, white].concat(),
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            width: 2,
+            height: 2,
+        }],
+    }
+}
+
+pub fn quad(pipeline: PipelineHandle, material: MaterialHandle, tint: [f32; 4]) -> MeshSource {
+    #[rustfmt::skip]
+    let vertices: [f32; 20] = [
+        -0.5, -0.5, 0.5, 0.0, 1.0,
+         0.5, -0.5, 0.5, 1.0, 1.0,
+         0.5,  0.5, 0.5, 1.0, 0.0,
+        -0.5,  0.5, 0.5, 0.0, 0.0,
+    ];
+    let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+    MeshSource {
+        name: String::from("quad"),
+        sub_meshes: vec![SubMeshSource {
+            vertices: bytemuck::cast_slice(&vertices).to_vec(),
+            indices: bytemuck::cast_slice(&indices).to_vec(),
+            index_count: indices.len() as u32,
+            index_type: wgpu::IndexFormat::Uint16,
+            material,
+        }],
+        mesh_uniform_group: UniformGroupSource {
+            name: String::from("quad"),
+            uniform_sources: vec![UniformSource {
+                data: bytemuck::cast_slice(&tint).to_vec(),
+                binding_type: BufferBindingType::Uniform,
+            }],
+        },
+        pipeline,
+    }
+}
+
+// A headless renderer with the textured quad registered, the scene most tests
+// start from
+pub struct TexturedScene {
+    pub renderer: Renderer<'static>,
+    pub pipeline: PipelineHandle,
+    pub material: MaterialHandle,
+    pub mesh: MeshHandle,
+}
+
+pub fn textured_scene(width: u32, height: u32, tint: [f32; 4]) -> TexturedScene {
+    let mut renderer = headless_renderer(width, height).unwrap();
+    let pipeline = renderer.register_pipeline(&textured_pipeline()).unwrap();
+    let material = renderer
+        .register_material(&checkerboard_material())
+        .unwrap();
+    let mesh = renderer
+        .register_mesh(&quad(pipeline, material, tint))
+        .unwrap();
+    TexturedScene {
+        renderer,
+        pipeline,
+        material,
+        mesh,
+    }
+}
+
+// Draws a frame holding only the mesh and captures it
+pub fn render_single(renderer: &mut Renderer, mesh: MeshHandle) -> RgbaImage {
+    let mut frame = renderer.begin_frame();
+    renderer.submit_mesh(&mut frame, mesh).unwrap();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+    image
+}
+
+// The camera global of the built-in pipelines
+pub fn camera(view_projection: Mat4) -> UniformSource {
+    let camera = [
+        view_projection.to_cols_array().as_slice(),
+        &[0.0, 0.0, -1.0, 1.0],
+    ]
+    .concat();
+    UniformSource {
+        data: bytemuck::cast_slice(&camera).to_vec(),
+        binding_type: BufferBindingType::Uniform,
+    }
+}
+
+// The mesh uniform of the unlit built-in pipelines
+pub fn model_color(model: Mat4, color: [f32; 4]) -> UniformSource {
+    let uniform = [model.to_cols_array().as_slice(), &color].concat();
+    UniformSource {
+        data: bytemuck::cast_slice(&uniform).to_vec(),
+        binding_type: BufferBindingType::Uniform,
+    }
+}
+
+// A quad of positions only, for the unlit color pipeline
+pub fn position_quad(
+    pipeline: PipelineHandle,
+    material: MaterialHandle,
+    mesh_uniform: UniformSource,
+) -> MeshSource {
+    #[rustfmt::skip]
+    let vertices: [f32; 12] = [
+        -0.5, -0.5, 0.5,
+         0.5, -0.5, 0.5,
+         0.5,  0.5, 0.5,
+        -0.5,  0.5, 0.5,
+    ];
+    let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+    MeshSource {
+        name: String::from("quad"),
+        sub_meshes: vec![SubMeshSource {
+            vertices: bytemuck::cast_slice(&vertices).to_vec(),
+            indices: bytemuck::cast_slice(&indices).to_vec(),
+            index_count: indices.len() as u32,
+            index_type: wgpu::IndexFormat::Uint16,
+            material,
+        }],
+        mesh_uniform_group: UniformGroupSource {
+            name: String::from("quad"),
+            uniform_sources: vec![mesh_uniform],
+        },
+        pipeline,
+    }
+}
@@ -0,0 +1,153 @@
+mod common;
+
+use common::{check_golden, textured_scene, TexturedScene, HEIGHT, WIDTH};
+use glam::{Mat4, Quat, Vec2, Vec3};
+use image::RgbaImage;
+use renderer2::{testing::headless_renderer, DebugDraw, DebugStyle, Viewport};
+use std::time::Duration;
+
+#[test]
+fn debug_primitives() {
+    let TexturedScene {
+        mut renderer, mesh, ..
+    } = textured_scene(WIDTH, HEIGHT, [1.0; 4]);
+
+    // The quad sits at a depth of 0.5 so depth tested lines behind it are
+    // hidden where they cross it
+    let behind = |x: f32, y: f32| Vec3::new(x, y, 0.9);
+    let mut debug_draw = DebugDraw::new(&renderer).unwrap();
+    let red = DebugStyle::new([1.0, 0.0, 0.0, 1.0]);
+    let green = DebugStyle::new([0.0, 1.0, 0.0, 1.0]);
+    let blue = DebugStyle::new([0.0, 0.0, 1.0, 1.0]);
+    debug_draw.add_line(behind(-0.9, 0.25), behind(0.9, 0.25), &red);
+    debug_draw.add_line(
+        behind(-0.9, -0.25),
+        behind(0.9, -0.25),
+        &DebugStyle {
+            depth_test: false,
+            ..red
+        },
+    );
+    debug_draw.add_cross(behind(-0.75, 0.75), 0.3, &green);
+    debug_draw.add_circle(behind(0.75, 0.75), Vec3::Z, 0.2, &green);
+    debug_draw.add_sphere(behind(-0.75, -0.75), 0.2, &blue);
+    debug_draw.add_triangle(
+        behind(0.6, -0.9),
+        behind(0.9, -0.9),
+        behind(0.75, -0.6),
+        &blue,
+    );
+    debug_draw.add_aabb(behind(-0.6, -0.6), behind(0.6, 0.6), &green);
+    debug_draw.add_obb(
+        Vec3::new(0.0, 0.0, 0.1),
+        Vec3::new(0.3, 0.3, 0.0),
+        Quat::from_rotation_z(std::f32::consts::FRAC_PI_4),
+        &DebugStyle::new([1.0, 1.0, 0.0, 1.0]),
+    );
+    debug_draw.add_axes(
+        Mat4::from_translation(Vec3::new(0.0, 0.0, 0.1)),
+        0.25,
+        &DebugStyle::default(),
+    );
+
+    let mut frame = renderer.begin_frame();
+    renderer.submit_mesh(&mut frame, mesh).unwrap();
+    renderer.draw_debug(&mut frame, &mut debug_draw).unwrap();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+
+    check_golden("debug_primitives", &image);
+}
+
+#[test]
+fn debug_primitives_expire() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let mut debug_draw = DebugDraw::new(&renderer).unwrap();
+    let style = DebugStyle::new([1.0, 0.0, 0.0, 1.0]);
+    debug_draw.add_line(Vec3::new(-1.0, 0.0, 0.5), Vec3::new(1.0, 0.0, 0.5), &style);
+    debug_draw.add_line(
+        Vec3::new(0.0, -1.0, 0.5),
+        Vec3::new(0.0, 1.0, 0.5),
+        &DebugStyle {
+            duration: Duration::from_secs(60),
+            ..style
+        },
+    );
+
+    // Whether the horizontal and vertical lines cross the left column and the
+    // top row of each frame
+    let red = |image: &RgbaImage, x: u32, y: u32| image.get_pixel(x, y).0 == [255, 0, 0, 255];
+    let mut visible = Vec::new();
+    for _ in 0..2 {
+        let mut frame = renderer.begin_frame();
+        renderer.draw_debug(&mut frame, &mut debug_draw).unwrap();
+        let image = renderer.capture_frame(&mut frame).unwrap();
+        renderer.end_frame(frame).unwrap();
+        visible.push((
+            (0..HEIGHT).any(|y| red(&image, 4, y)),
+            (0..WIDTH).any(|x| red(&image, x, 4)),
+        ));
+    }
+
+    // The single frame line is gone in the second frame, the other remains
+    assert_eq!(visible, vec![(true, true), (false, true)]);
+}
+
+#[test]
+fn debug_text() {
+    let mut renderer = headless_renderer(WIDTH * 2, HEIGHT).unwrap();
+    let mut debug_draw = DebugDraw::new(&renderer).unwrap();
+    debug_draw.add_screen_text(
+        Vec2::new(2.0, 2.0),
+        "FPS: 60\nabc {}~",
+        1.0,
+        &DebugStyle::new([1.0, 1.0, 0.0, 1.0]),
+    );
+    debug_draw.add_text(
+        Vec3::new(-0.5, -0.25, 0.5),
+        "Label",
+        2.0,
+        &DebugStyle::new([0.0, 0.0, 1.0, 1.0]),
+    );
+
+    let mut frame = renderer.begin_frame();
+    renderer.draw_debug(&mut frame, &mut debug_draw).unwrap();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+
+    check_golden("debug_text", &image);
+}
+
+#[test]
+fn debug_views_keep_their_cameras() {
+    let mut renderer = headless_renderer(WIDTH * 2, HEIGHT).unwrap();
+    let mut debug_draw = DebugDraw::new(&renderer).unwrap();
+    let style = DebugStyle::new([1.0, 0.0, 0.0, 1.0]);
+    debug_draw.add_line(Vec3::new(0.0, -1.0, 0.5), Vec3::new(0.0, 1.0, 0.5), &style);
+
+    // Enough lines outside the view to outgrow the initial vertex buffer
+    for i in 0..4096 {
+        let y = i as f32 / 4096.0;
+        debug_draw.add_line(Vec3::new(2.0, y, 0.5), Vec3::new(3.0, y, 0.5), &style);
+    }
+
+    // The left view moves the line right and the right view moves it left
+    let mut frame = renderer.begin_frame();
+    let width = WIDTH as f32;
+    let height = HEIGHT as f32;
+    for (x, offset) in [(0.0, 0.5), (width, -0.5)] {
+        renderer
+            .set_viewport(&mut frame, Some(Viewport::new(x, 0.0, width, height)))
+            .unwrap();
+        debug_draw.set_view_projection(Mat4::from_translation(Vec3::new(offset, 0.0, 0.0)));
+        renderer.draw_debug(&mut frame, &mut debug_draw).unwrap();
+    }
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+
+    let red = |x: u32| (x - 2..x + 2).any(|x| image.get_pixel(x, HEIGHT / 2).0 == [255, 0, 0, 255]);
+    assert!(red(WIDTH * 3 / 4));
+    assert!(!red(WIDTH / 4));
+    assert!(red(WIDTH + WIDTH / 4));
+    assert!(!red(WIDTH + WIDTH * 3 / 4));
+}
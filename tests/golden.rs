@@ -0,0 +1,230 @@
+mod common;
+
+use common::{
+    check_golden, checkerboard_material, quad, render_single, textured_pipeline, textured_scene,
+    TexturedScene, HEIGHT, WIDTH,
+};
+use renderer2::{
+    testing::{headless_configuration, headless_renderer},
+    Color, FrameStatistics, FrameStatus, Renderer, RendererError, Viewport,
+};
+
+#[test]
+fn textured_quad() {
+    let TexturedScene {
+        mut renderer, mesh, ..
+    } = textured_scene(WIDTH, HEIGHT, [1.0, 0.5, 0.25, 1.0]);
+    let image = render_single(&mut renderer, mesh);
+
+    check_golden("textured_quad", &image);
+}
+
+#[test]
+fn empty_frame_is_cleared() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let mut frame = renderer.begin_frame();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+
+    check_golden("empty_frame", &image);
+}
+
+#[test]
+fn resize_reallocates_targets() {
+    let TexturedScene {
+        mut renderer, mesh, ..
+    } = textured_scene(WIDTH, HEIGHT, [1.0; 4]);
+
+    renderer.resize(0, HEIGHT);
+    assert!(renderer.is_paused());
+    let mut frame = renderer.begin_frame();
+    assert!(matches!(
+        renderer.capture_frame(&mut frame),
+        Err(RendererError::RenderingPaused)
+    ));
+    assert_eq!(renderer.end_frame(frame).unwrap(), FrameStatus::Skipped);
+
+    renderer.resize(WIDTH * 2, HEIGHT);
+    assert!(!renderer.is_paused());
+    assert_eq!(renderer.size(), (WIDTH * 2, HEIGHT));
+
+    let mut frame = renderer.begin_frame();
+    renderer.submit_mesh(&mut frame, mesh).unwrap();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    assert_eq!(renderer.end_frame(frame).unwrap(), FrameStatus::Presented);
+    assert_eq!(image.dimensions(), (WIDTH * 2, HEIGHT));
+
+    check_golden("resized_quad", &image);
+}
+
+#[test]
+fn frames_minimized_mid_frame_are_discarded() {
+    let TexturedScene {
+        mut renderer, mesh, ..
+    } = textured_scene(WIDTH, HEIGHT, [1.0; 4]);
+
+    // Some of the draws are recorded and some still queued when the window
+    // is minimized
+    let mut frame = renderer.begin_frame();
+    for _ in 0..7 {
+        renderer.submit_mesh(&mut frame, mesh).unwrap();
+    }
+    renderer.resize(0, 0);
+    assert_eq!(renderer.end_frame(frame).unwrap(), FrameStatus::Skipped);
+    assert_eq!(renderer.last_frame_statistics(), FrameStatistics::default());
+
+    renderer.resize(WIDTH, HEIGHT);
+    let mut frame = renderer.begin_frame();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+    assert_eq!(
+        renderer.last_frame_statistics(),
+        FrameStatistics {
+            submissions: 1,
+            render_passes: 1,
+            draws: 0,
+        }
+    );
+
+    check_golden("empty_frame", &image);
+}
+
+#[test]
+fn stale_handles_are_rejected() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let pipeline = renderer.register_pipeline(&textured_pipeline()).unwrap();
+    assert_eq!(
+        renderer.register_pipeline(&textured_pipeline()).unwrap(),
+        pipeline
+    );
+
+    let material = renderer
+        .register_material(&checkerboard_material())
+        .unwrap();
+    let mesh = renderer
+        .register_mesh(&quad(pipeline, material, [1.0; 4]))
+        .unwrap();
+    renderer.unregister_material(material).unwrap();
+    let replacement = renderer
+        .register_material(&checkerboard_material())
+        .unwrap();
+    assert_ne!(material, replacement);
+    assert!(matches!(
+        renderer.register_mesh(&quad(pipeline, material, [1.0; 4])),
+        Err(RendererError::UnknownMaterial)
+    ));
+
+    let mut frame = renderer.begin_frame();
+    assert!(matches!(
+        renderer.submit_mesh(&mut frame, mesh),
+        Err(RendererError::UnknownMaterial)
+    ));
+    renderer.end_frame(frame).unwrap();
+}
+
+#[test]
+fn unregistered_resources_are_not_drawn() {
+    let TexturedScene {
+        mut renderer,
+        material,
+        mesh,
+        ..
+    } = textured_scene(WIDTH, HEIGHT, [1.0; 4]);
+
+    // Queued draws referencing the material are purged with it
+    let mut frame = renderer.begin_frame();
+    renderer.submit_mesh(&mut frame, mesh).unwrap();
+    renderer.unregister_material(material).unwrap();
+    renderer.end_frame(frame).unwrap();
+    assert!(matches!(
+        renderer.unregister_material(material),
+        Err(RendererError::InvalidHandle)
+    ));
+
+    renderer.unregister_mesh(mesh).unwrap();
+    let mut frame = renderer.begin_frame();
+    assert!(matches!(
+        renderer.submit_mesh(&mut frame, mesh),
+        Err(RendererError::InvalidHandle)
+    ));
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+
+    check_golden("empty_frame", &image);
+}
+
+#[test]
+fn adapter_can_be_selected_by_name() {
+    let mut configuration = headless_configuration(WIDTH, HEIGHT).unwrap();
+    let adapters = Renderer::enumerate_adapters(&configuration);
+    assert!(!adapters.is_empty());
+
+    configuration.adapter_name = Some(adapters[0].name.clone());
+    assert!(Renderer::new_headless(&configuration).is_ok());
+
+    configuration.adapter_name = Some(String::from("no such adapter"));
+    assert!(matches!(
+        Renderer::new_headless(&configuration),
+        Err(RendererError::AdapterRequest)
+    ));
+}
+
+#[test]
+fn clear_color_can_change_per_frame() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    renderer.set_clear_color(Some(Color::RED));
+    let mut frame = renderer.begin_frame();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+    assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+
+    // Without a clear the previous frame is kept
+    renderer.set_clear_color(None);
+    let mut frame = renderer.begin_frame();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+    assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+
+    renderer.set_clear_color(Some(Color::TRANSPARENT));
+    let mut frame = renderer.begin_frame();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+    assert_eq!(image.get_pixel(0, 0).0, [0, 0, 0, 0]);
+}
+
+#[test]
+fn viewports_split_the_frame() {
+    let TexturedScene {
+        mut renderer,
+        pipeline,
+        material,
+        mesh: left,
+    } = textured_scene(WIDTH * 2, HEIGHT, [1.0, 0.0, 0.0, 1.0]);
+    let right = renderer
+        .register_mesh(&quad(pipeline, material, [0.0, 0.0, 1.0, 1.0]))
+        .unwrap();
+
+    let mut frame = renderer.begin_frame();
+    let width = WIDTH as f32;
+    let height = HEIGHT as f32;
+    assert!(matches!(
+        renderer.set_viewport(
+            &mut frame,
+            Some(Viewport::new(width, 0.0, width * 2.0, height))
+        ),
+        Err(RendererError::InvalidViewport(_))
+    ));
+
+    renderer
+        .set_viewport(&mut frame, Some(Viewport::new(0.0, 0.0, width, height)))
+        .unwrap();
+    renderer.submit_mesh(&mut frame, left).unwrap();
+    renderer
+        .set_viewport(&mut frame, Some(Viewport::new(width, 0.0, width, height)))
+        .unwrap();
+    renderer.submit_mesh(&mut frame, right).unwrap();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+
+    check_golden("split_viewports", &image);
+}
@@ -0,0 +1,399 @@
+mod common;
+
+use common::{
+    camera, check_golden, checkerboard_material, quad, render_single, shader_path,
+    textured_pipeline, textured_scene, TexturedScene, HEIGHT, WIDTH,
+};
+use glam::{Mat4, Quat, Vec2, Vec3};
+use renderer2::{
+    testing::{bind_group_layout_count, headless_renderer},
+    BufferBindingType, MeshSource, PipelineConfiguration, Renderer, RendererError, ShaderSource,
+    SubMeshSource, UniformGroupSource, UniformSource,
+};
+use std::time::Duration;
+
+#[test]
+fn builtin_pipelines() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let material = renderer
+        .register_material(&checkerboard_material())
+        .unwrap();
+
+    // Every built-in shares the camera at binding 0 of the pipeline globals,
+    // the lit pipeline adds a light shining into the screen
+    #[rustfmt::skip]
+    let light: [f32; 12] = [
+        0.5, -0.5, 1.0, 0.0, // direction
+        1.0, 1.0, 1.0, 1.0, // color
+        0.2, 0.2, 0.2, 1.0, // ambient
+    ];
+
+    // One quad per quadrant, the attributes after the position differ
+    let corners = [[-0.5, -0.5], [0.5, -0.5], [0.5, 0.5], [-0.5, 0.5]];
+    let uv = |[x, y]: [f32; 2]| vec![x + 0.5, 0.5 - y];
+    let quadrants = [
+        (PipelineConfiguration::unlit_color(), Vec2::new(-0.5, 0.5)),
+        (PipelineConfiguration::unlit_textured(), Vec2::new(0.5, 0.5)),
+        (PipelineConfiguration::vertex_color(), Vec2::new(-0.5, -0.5)),
+        (PipelineConfiguration::blinn_phong(), Vec2::new(0.5, -0.5)),
+    ];
+    let mut meshes = Vec::new();
+    for (i, (configuration, center)) in quadrants.into_iter().enumerate() {
+        let lit = i == 3;
+        let pipeline = renderer.register_pipeline(&configuration).unwrap();
+        let mut globals = vec![camera(Mat4::IDENTITY)];
+        if lit {
+            globals.push(UniformSource {
+                data: bytemuck::cast_slice(&light).to_vec(),
+                binding_type: BufferBindingType::Uniform,
+            });
+        }
+        renderer
+            .add_pipeline_global(
+                pipeline,
+                &UniformGroupSource {
+                    name: String::from("globals"),
+                    uniform_sources: globals,
+                },
+            )
+            .unwrap();
+
+        let mut vertices: Vec<f32> = Vec::new();
+        for (corner, [x, y]) in corners.into_iter().enumerate() {
+            vertices.extend([x, y, 0.5]);
+            vertices.extend(match i {
+                1 => uv([x, y]),
+                2 => {
+                    let mut color = vec![0.0, 0.0, 0.0, 1.0];
+                    color[corner % 3] = 1.0;
+                    color
+                }
+                3 => [vec![0.0, 0.0, -1.0], uv([x, y])].concat(),
+                _ => Vec::new(),
+            });
+        }
+        let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+        let model = Mat4::from_scale_rotation_translation(
+            Vec3::splat(0.8),
+            Quat::IDENTITY,
+            center.extend(0.0),
+        );
+        let mut mesh_uniform = [model.to_cols_array().as_slice(), &[1.0, 0.5, 0.25, 1.0]].concat();
+        if lit {
+            // Specular color and shininess
+            mesh_uniform.extend([1.0, 1.0, 1.0, 16.0]);
+        }
+
+        let mesh = renderer
+            .register_mesh(&MeshSource {
+                name: String::from("quad"),
+                sub_meshes: vec![SubMeshSource {
+                    vertices: bytemuck::cast_slice(&vertices).to_vec(),
+                    indices: bytemuck::cast_slice(&indices).to_vec(),
+                    index_count: indices.len() as u32,
+                    index_type: wgpu::IndexFormat::Uint16,
+                    material,
+                }],
+                mesh_uniform_group: UniformGroupSource {
+                    name: String::from("quad"),
+                    uniform_sources: vec![UniformSource {
+                        data: bytemuck::cast_slice(&mesh_uniform).to_vec(),
+                        binding_type: BufferBindingType::Uniform,
+                    }],
+                },
+                pipeline,
+            })
+            .unwrap();
+        meshes.push(mesh);
+    }
+
+    let mut frame = renderer.begin_frame();
+    for mesh in meshes {
+        renderer.submit_mesh(&mut frame, mesh).unwrap();
+    }
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+
+    check_golden("builtin_pipelines", &image);
+}
+
+#[test]
+fn inline_shader_sources() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let inline = PipelineConfiguration {
+        shader_source: ShaderSource::Wgsl(include_str!("shaders/textured.wgsl").into()),
+        ..textured_pipeline()
+    };
+    let pipeline = renderer.register_pipeline(&inline).unwrap();
+
+    // Equal sources share a pipeline, even when built separately
+    let generated = PipelineConfiguration {
+        shader_source: ShaderSource::Wgsl(
+            String::from(include_str!("shaders/textured.wgsl")).into(),
+        ),
+        ..textured_pipeline()
+    };
+    assert_eq!(renderer.register_pipeline(&generated).unwrap(), pipeline);
+    assert_ne!(
+        renderer.register_pipeline(&textured_pipeline()).unwrap(),
+        pipeline
+    );
+
+    let material = renderer
+        .register_material(&checkerboard_material())
+        .unwrap();
+    let mesh = renderer
+        .register_mesh(&quad(pipeline, material, [1.0, 0.5, 0.25, 1.0]))
+        .unwrap();
+    let image = render_single(&mut renderer, mesh);
+
+    check_golden("textured_quad", &image);
+}
+
+#[cfg(feature = "spirv")]
+#[test]
+fn malformed_spirv_is_rejected() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let configuration = PipelineConfiguration {
+        shader_source: ShaderSource::SpirV(include_bytes!("shaders/textured.wgsl").into()),
+        ..textured_pipeline()
+    };
+    assert!(matches!(
+        renderer.register_pipeline(&configuration),
+        Err(RendererError::ShaderCompile(_))
+    ));
+}
+
+#[test]
+fn shaders_reload_in_place() {
+    let directory = std::env::temp_dir().join(format!("renderer2-reload-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("textured.wgsl");
+    let original = include_str!("shaders/textured.wgsl");
+
+    // Rewrites the shader and moves its modification time forward, the
+    // filesystem may not resolve two writes in quick succession
+    let mut version = 0;
+    let mut write_shader = |source: &str| {
+        std::fs::write(&path, source).unwrap();
+        version += 1;
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(std::time::SystemTime::UNIX_EPOCH + Duration::from_secs(version))
+            .unwrap();
+    };
+    write_shader(original);
+
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let pipeline = renderer
+        .register_pipeline(&PipelineConfiguration {
+            shader_source: ShaderSource::Path(path.clone()),
+            ..textured_pipeline()
+        })
+        .unwrap();
+    let material = renderer
+        .register_material(&checkerboard_material())
+        .unwrap();
+    let mesh = renderer
+        .register_mesh(&quad(pipeline, material, [1.0, 1.0, 1.0, 1.0]))
+        .unwrap();
+    let corner_pixel = |renderer: &mut Renderer| {
+        render_single(renderer, mesh)
+            .get_pixel(WIDTH / 4 + 2, HEIGHT / 4 + 2)
+            .0
+    };
+
+    assert_eq!(renderer.reload_shaders(), 0);
+    assert_eq!(corner_pixel(&mut renderer), [255, 255, 255, 255]);
+
+    write_shader(&original.replace(
+        "return textureSample(albedo, albedo_sampler, input.uv) * mesh.tint;",
+        "return vec4<f32>(1.0, 0.0, 0.0, 1.0);",
+    ));
+    assert_eq!(renderer.reload_shaders(), 1);
+    assert_eq!(corner_pixel(&mut renderer), [255, 0, 0, 255]);
+
+    // A shader that fails to compile leaves the last working one in place
+    write_shader(&original.replace("fn fs_main", "fn fs_main_renamed"));
+    assert_eq!(renderer.reload_shaders(), 0);
+    assert_eq!(corner_pixel(&mut renderer), [255, 0, 0, 255]);
+
+    // So does one whose uniforms outgrow the buffers of the registered mesh
+    write_shader(&original.replace(
+        "tint: vec4<f32>,",
+        "tint: vec4<f32>,\n    emissive: vec4<f32>,",
+    ));
+    assert_eq!(renderer.reload_shaders(), 0);
+    assert_eq!(corner_pixel(&mut renderer), [255, 0, 0, 255]);
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn shader_errors_are_returned() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let path = shader_path("broken.wgsl");
+    let broken = PipelineConfiguration {
+        shader_source: ShaderSource::Path(path.clone()),
+        ..textured_pipeline()
+    };
+    match renderer.register_pipeline(&broken) {
+        Err(RendererError::ShaderCompile(error)) => {
+            assert_eq!(
+                (error.file.as_str(), error.line, error.column),
+                (path.to_str().unwrap(), Some(10), Some(12))
+            );
+        }
+        other => panic!(
+            "expected a shader compile error, got {:?}",
+            other.map(|_| ())
+        ),
+    }
+
+    // Errors only the device finds are captured rather than panicking
+    let missing_entry_point = PipelineConfiguration {
+        fragment_shader_entrypoint: String::from("missing"),
+        ..textured_pipeline()
+    };
+    assert!(matches!(
+        renderer.register_pipeline(&missing_entry_point),
+        Err(RendererError::PipelineCreation { .. })
+    ));
+
+    // Layouts the device rejects are not kept for later pipelines, here a
+    // writable storage buffer in the vertex stage without the feature for it
+    let layout_count = bind_group_layout_count(&renderer);
+    let mut invalid_layout = textured_pipeline();
+    invalid_layout.bind_group_layouts[0][0].ty = wgpu::BindingType::Buffer {
+        ty: BufferBindingType::Storage { read_only: false },
+        has_dynamic_offset: false,
+        min_binding_size: None,
+    };
+    invalid_layout.bind_group_layouts[0][0].visibility = wgpu::ShaderStages::VERTEX;
+    for _ in 0..2 {
+        assert!(matches!(
+            renderer.register_pipeline(&invalid_layout),
+            Err(RendererError::PipelineCreation { .. })
+        ));
+    }
+    assert_eq!(bind_group_layout_count(&renderer), layout_count);
+
+    // None of the failures leaves a pipeline behind
+    assert!(renderer.register_pipeline(&textured_pipeline()).is_ok());
+}
+
+#[test]
+fn reflected_layouts_match_the_shader() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let reflected =
+        PipelineConfiguration::reflect(textured_pipeline().shader_source.clone()).unwrap();
+    assert_eq!(
+        reflected.vertex_buffer_layouts,
+        textured_pipeline().vertex_buffer_layouts
+    );
+    assert_eq!(
+        reflected.bind_group_layouts,
+        textured_pipeline().bind_group_layouts
+    );
+    assert_eq!(reflected.vertex_shader_entrypoint, "vs_main");
+    assert_eq!(reflected.fragment_shader_entrypoint, "fs_main");
+
+    let pipeline = renderer.register_pipeline(&reflected).unwrap();
+    let material = renderer
+        .register_material(&checkerboard_material())
+        .unwrap();
+    let mesh = renderer
+        .register_mesh(&quad(pipeline, material, [1.0, 0.5, 0.25, 1.0]))
+        .unwrap();
+    let image = render_single(&mut renderer, mesh);
+
+    check_golden("textured_quad", &image);
+}
+
+#[test]
+fn mismatched_bind_groups_are_rejected() {
+    let TexturedScene {
+        mut renderer,
+        pipeline,
+        material,
+        ..
+    } = textured_scene(WIDTH, HEIGHT, [1.0; 4]);
+
+    // The shader's mesh uniform is a vec4
+    let mut too_small = quad(pipeline, material, [1.0; 4]);
+    too_small.mesh_uniform_group.uniform_sources[0]
+        .data
+        .truncate(8);
+    assert!(matches!(
+        renderer.register_mesh(&too_small),
+        Err(RendererError::BindGroupMismatch { group: 0, .. })
+    ));
+
+    let mut extra_uniform = quad(pipeline, material, [1.0; 4]);
+    extra_uniform
+        .mesh_uniform_group
+        .uniform_sources
+        .push(UniformSource {
+            data: vec![0; 16],
+            binding_type: BufferBindingType::Uniform,
+        });
+    assert!(matches!(
+        renderer.register_mesh(&extra_uniform),
+        Err(RendererError::BindGroupMismatch { group: 0, .. })
+    ));
+
+    // The shader samples a single texture
+    let mut two_textures = checkerboard_material();
+    two_textures
+        .texture_sources
+        .push(checkerboard_material().texture_sources.remove(0));
+    let two_textures = renderer.register_material(&two_textures).unwrap();
+    assert!(matches!(
+        renderer.register_mesh(&quad(pipeline, two_textures, [1.0; 4])),
+        Err(RendererError::BindGroupMismatch { group: 1, .. })
+    ));
+
+    assert!(renderer
+        .register_mesh(&quad(pipeline, material, [1.0; 4]))
+        .is_ok());
+}
+
+#[test]
+fn bind_group_layouts_are_shared() {
+    let TexturedScene {
+        mut renderer,
+        pipeline: textured,
+        material,
+        ..
+    } = textured_scene(WIDTH, HEIGHT, [1.0; 4]);
+    let unlit = renderer
+        .register_pipeline(&PipelineConfiguration::unlit_textured())
+        .unwrap();
+    renderer
+        .add_pipeline_global(
+            unlit,
+            &UniformGroupSource {
+                name: String::from("camera"),
+                uniform_sources: vec![camera(Mat4::IDENTITY)],
+            },
+        )
+        .unwrap();
+
+    // One layout holding a texture and sampler shared by both pipelines and
+    // the material, one holding a uniform shared by the unlit pipeline's
+    // groups and the camera, and one for the textured mesh, whose uniform
+    // only the fragment stage reads
+    assert_eq!(bind_group_layout_count(&renderer), 3);
+
+    // A group that does not match the configured layout is rejected before
+    // a bind group is created for it
+    let mut mismatched = quad(textured, material, [1.0; 4]);
+    mismatched.mesh_uniform_group.uniform_sources.clear();
+    assert!(matches!(
+        renderer.register_mesh(&mismatched),
+        Err(RendererError::BindGroupMismatch { group: 0, .. })
+    ));
+}
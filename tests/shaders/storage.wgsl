@@ -0,0 +1,23 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+// x indexes the palette
+@group(0) @binding(0)
+var<uniform> palette_index: vec4<u32>;
+@group(0) @binding(1)
+var<storage, read> palette: array<vec4<f32>>;
+@group(0) @binding(2)
+var<storage, read_write> fragment_count: atomic<u32>;
+
+@vertex
+fn vs_main(input: VertexInput) -> @builtin(position) vec4<f32> {
+    return vec4<f32>(input.position, 1.0);
+}
+
+@fragment
+fn fs_main() -> @location(0) vec4<f32> {
+    atomicAdd(&fragment_count, 1u);
+    return palette[palette_index.x];
+}
@@ -0,0 +1,34 @@
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+struct MeshUniform {
+    tint: vec4<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> mesh: MeshUniform;
+
+@group(1) @binding(0)
+var albedo: texture_2d<f32>;
+@group(1) @binding(1)
+var albedo_sampler: sampler;
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var output: VertexOutput;
+    output.position = vec4<f32>(input.position, 1.0);
+    output.uv = input.uv;
+    return output;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    return textureSample(albedo, albedo_sampler, input.uv) * mesh.tint;
+}
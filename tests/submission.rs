@@ -0,0 +1,96 @@
+mod common;
+
+use common::{check_golden, quad, textured_pipeline, textured_scene, TexturedScene, HEIGHT, WIDTH};
+use renderer2::{
+    testing::queue_unchecked, FixedCount, FrameStatistics, OncePerFrame, PipelineConfiguration,
+    RendererError, SubmissionPolicy,
+};
+
+#[test]
+fn once_per_frame_policy_records_one_encoder() {
+    let TexturedScene {
+        mut renderer, mesh, ..
+    } = textured_scene(WIDTH, HEIGHT, [1.0, 0.5, 0.25, 1.0]);
+
+    let mut images = Vec::new();
+    let policies: [Box<dyn SubmissionPolicy>; 2] =
+        [Box::new(FixedCount::default()), Box::new(OncePerFrame)];
+    for policy in policies {
+        renderer.set_submission_policy(policy);
+        let mut frame = renderer.begin_frame();
+        for _ in 0..12 {
+            renderer.submit_mesh(&mut frame, mesh).unwrap();
+        }
+        images.push(renderer.capture_frame(&mut frame).unwrap());
+        renderer.end_frame(frame).unwrap();
+    }
+    assert_eq!(images[0], images[1]);
+    check_golden("textured_quad", &images[1]);
+
+    renderer.set_submission_policy(Box::new(FixedCount::default()));
+    let mut frame = renderer.begin_frame();
+    for _ in 0..12 {
+        renderer.submit_mesh(&mut frame, mesh).unwrap();
+    }
+    renderer.end_frame(frame).unwrap();
+    assert_eq!(
+        renderer.last_frame_statistics(),
+        FrameStatistics {
+            submissions: 3,
+            render_passes: 3,
+            draws: 12,
+        }
+    );
+
+    renderer.set_submission_policy(Box::new(OncePerFrame));
+    let mut frame = renderer.begin_frame();
+    for _ in 0..12 {
+        renderer.submit_mesh(&mut frame, mesh).unwrap();
+    }
+    renderer.end_frame(frame).unwrap();
+    assert_eq!(
+        renderer.last_frame_statistics(),
+        FrameStatistics {
+            submissions: 1,
+            render_passes: 1,
+            draws: 12,
+        }
+    );
+}
+
+#[test]
+fn failed_recording_leaves_no_draws_queued() {
+    let TexturedScene {
+        mut renderer,
+        pipeline,
+        material,
+        mesh,
+    } = textured_scene(WIDTH, HEIGHT, [1.0, 0.5, 0.25, 1.0]);
+
+    // Recorded after the first pipeline, which fails
+    let other_pipeline = renderer
+        .register_pipeline(&PipelineConfiguration {
+            depth_compare_function: wgpu::CompareFunction::Always,
+            ..textured_pipeline()
+        })
+        .unwrap();
+    let other_mesh = renderer
+        .register_mesh(&quad(other_pipeline, material, [1.0, 0.5, 0.25, 1.0]))
+        .unwrap();
+    renderer.unregister_mesh(mesh).unwrap();
+
+    let mut frame = renderer.begin_frame();
+    renderer.submit_mesh(&mut frame, other_mesh).unwrap();
+    queue_unchecked(&mut renderer, pipeline, mesh);
+    assert!(matches!(
+        renderer.end_frame(frame),
+        Err(RendererError::InvalidHandle)
+    ));
+    assert_eq!(renderer.last_frame_statistics().draws, 1);
+
+    let mut frame = renderer.begin_frame();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+    check_golden("empty_frame", &image);
+    assert_eq!(renderer.last_frame_statistics().draws, 0);
+}
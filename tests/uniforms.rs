@@ -0,0 +1,349 @@
+mod common;
+
+use common::{
+    camera, check_golden, checkerboard_material, model_color, position_quad, quad, render_single,
+    shader_source, textured_pipeline, textured_scene, TexturedScene, HEIGHT, WIDTH,
+};
+use glam::{Mat4, Vec3, Vec4};
+use renderer2::{
+    testing::headless_renderer, BufferBindingType, FixedCount, OncePerFrame, PipelineConfiguration,
+    RendererError, SubmissionPolicy, TypedUniform, UniformGroupSource, UniformSource, Viewport,
+};
+
+#[test]
+fn uniforms_update_in_place() {
+    let TexturedScene {
+        mut renderer,
+        pipeline,
+        mesh,
+        ..
+    } = textured_scene(WIDTH * 2, HEIGHT, [1.0, 0.0, 0.0, 1.0]);
+    let blue: [f32; 4] = [0.0, 0.0, 1.0, 1.0];
+
+    assert!(matches!(
+        renderer.update_mesh_uniform(mesh, 0, &[0; 8]),
+        Err(RendererError::UniformSize {
+            expected: 16,
+            actual: 8
+        })
+    ));
+    assert!(matches!(
+        renderer.update_mesh_uniform(mesh, 1, bytemuck::cast_slice(&blue)),
+        Err(RendererError::UnknownUniform)
+    ));
+    assert!(matches!(
+        renderer.update_pipeline_global(pipeline, 0, 0, &[0; 16]),
+        Err(RendererError::UnknownUniform)
+    ));
+
+    // The draw recorded before the update keeps the old tint
+    let mut frame = renderer.begin_frame();
+    let width = WIDTH as f32;
+    let height = HEIGHT as f32;
+    renderer
+        .set_viewport(&mut frame, Some(Viewport::new(0.0, 0.0, width, height)))
+        .unwrap();
+    renderer.submit_mesh(&mut frame, mesh).unwrap();
+    renderer
+        .set_viewport(&mut frame, Some(Viewport::new(width, 0.0, width, height)))
+        .unwrap();
+    renderer
+        .update_mesh_uniform(mesh, 0, bytemuck::cast_slice(&blue))
+        .unwrap();
+    renderer.submit_mesh(&mut frame, mesh).unwrap();
+    let image = renderer.capture_frame(&mut frame).unwrap();
+    renderer.end_frame(frame).unwrap();
+
+    check_golden("split_viewports", &image);
+}
+
+#[test]
+fn pipeline_globals_update_in_place() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let pipeline = renderer
+        .register_pipeline(&PipelineConfiguration::unlit_color())
+        .unwrap();
+    let translated = |offset: f32| camera(Mat4::from_translation(Vec3::new(offset, 0.0, 0.0)));
+    renderer
+        .add_pipeline_global(
+            pipeline,
+            &UniformGroupSource {
+                name: String::from("camera"),
+                uniform_sources: vec![translated(0.0)],
+            },
+        )
+        .unwrap();
+
+    let material = renderer
+        .register_material(&checkerboard_material())
+        .unwrap();
+    let mesh = renderer
+        .register_mesh(&position_quad(
+            pipeline,
+            material,
+            model_color(Mat4::IDENTITY, [1.0; 4]),
+        ))
+        .unwrap();
+
+    // Another global would move the group the mesh is bound to
+    assert!(matches!(
+        renderer.add_pipeline_global(
+            pipeline,
+            &UniformGroupSource {
+                name: String::from("camera"),
+                uniform_sources: vec![translated(0.0)],
+            },
+        ),
+        Err(RendererError::PipelineHasMeshes)
+    ));
+
+    // Moving the camera moves the quad into the right half
+    renderer
+        .update_pipeline_global(pipeline, 0, 0, &translated(0.5).data)
+        .unwrap();
+    let image = render_single(&mut renderer, mesh);
+
+    let background = *image.get_pixel(0, 0);
+    assert_eq!(*image.get_pixel(WIDTH / 8, HEIGHT / 2), background);
+    assert_eq!(
+        image.get_pixel(WIDTH * 7 / 8, HEIGHT / 2).0,
+        [255, 255, 255, 255]
+    );
+}
+
+renderer2::wgsl_struct! {
+    struct Camera {
+        view_projection: Mat4,
+        position: Vec4,
+    }
+}
+
+renderer2::wgsl_struct! {
+    struct MeshUniform {
+        model: Mat4,
+        color: Vec4,
+    }
+}
+
+#[test]
+fn views_use_the_globals_set_before_their_draws() {
+    let mut renderer = headless_renderer(WIDTH * 2, HEIGHT).unwrap();
+    let pipeline = renderer
+        .register_pipeline(&PipelineConfiguration::unlit_color())
+        .unwrap();
+    let translated = |offset: f32| camera(Mat4::from_translation(Vec3::new(offset, 0.0, 0.0)));
+    renderer
+        .add_pipeline_global(
+            pipeline,
+            &UniformGroupSource {
+                name: String::from("camera"),
+                uniform_sources: vec![translated(0.5)],
+            },
+        )
+        .unwrap();
+    let material = renderer
+        .register_material(&checkerboard_material())
+        .unwrap();
+    let mesh = renderer
+        .register_mesh(&position_quad(
+            pipeline,
+            material,
+            model_color(Mat4::IDENTITY, [1.0; 4]),
+        ))
+        .unwrap();
+
+    // The camera of the left view moves the quad right, the one of the right
+    // view moves it left. The left draw is still queued when the camera
+    // changes.
+    let policies: [Box<dyn SubmissionPolicy>; 2] =
+        [Box::new(FixedCount::default()), Box::new(OncePerFrame)];
+    let width = WIDTH as f32;
+    let height = HEIGHT as f32;
+    for policy in policies {
+        renderer.set_submission_policy(policy);
+        renderer
+            .update_pipeline_global(pipeline, 0, 0, &translated(0.5).data)
+            .unwrap();
+        let mut frame = renderer.begin_frame();
+        renderer
+            .set_viewport(&mut frame, Some(Viewport::new(0.0, 0.0, width, height)))
+            .unwrap();
+        renderer.submit_mesh(&mut frame, mesh).unwrap();
+        renderer
+            .update_pipeline_global(pipeline, 0, 0, &translated(-0.5).data)
+            .unwrap();
+        renderer
+            .set_viewport(&mut frame, Some(Viewport::new(width, 0.0, width, height)))
+            .unwrap();
+        renderer.submit_mesh(&mut frame, mesh).unwrap();
+        let image = renderer.capture_frame(&mut frame).unwrap();
+        renderer.end_frame(frame).unwrap();
+
+        let background = *image.get_pixel(0, 0);
+        let white = [255, 255, 255, 255];
+        let y = HEIGHT / 2;
+        assert_eq!(*image.get_pixel(WIDTH * 3 / 8, y), background);
+        assert_eq!(image.get_pixel(WIDTH * 7 / 8, y).0, white);
+        assert_eq!(image.get_pixel(WIDTH + WIDTH / 8, y).0, white);
+        assert_eq!(*image.get_pixel(WIDTH + WIDTH * 5 / 8, y), background);
+    }
+
+    // Staging the update keeps the frame in a single submission
+    assert_eq!(renderer.last_frame_statistics().submissions, 1);
+}
+
+#[test]
+fn typed_uniforms_bind_to_shaders() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let pipeline = renderer
+        .register_pipeline(&PipelineConfiguration::unlit_color())
+        .unwrap();
+    let camera = TypedUniform::new(Camera {
+        view_projection: Mat4::IDENTITY,
+        position: Vec4::new(0.0, 0.0, -1.0, 1.0),
+    });
+    renderer
+        .add_pipeline_global(
+            pipeline,
+            &UniformGroupSource {
+                name: String::from("camera"),
+                uniform_sources: vec![camera.source()],
+            },
+        )
+        .unwrap();
+
+    let material = renderer
+        .register_material(&checkerboard_material())
+        .unwrap();
+    let mut mesh_uniform = TypedUniform::new(MeshUniform {
+        model: Mat4::IDENTITY,
+        color: Vec4::new(1.0, 0.0, 0.0, 1.0),
+    });
+    let mesh = renderer
+        .register_mesh(&position_quad(pipeline, material, (&mesh_uniform).into()))
+        .unwrap();
+
+    mesh_uniform.set(MeshUniform {
+        color: Vec4::new(0.0, 1.0, 0.0, 1.0),
+        ..*mesh_uniform.value()
+    });
+    renderer
+        .update_mesh_uniform(mesh, 0, mesh_uniform.bytes())
+        .unwrap();
+    let image = render_single(&mut renderer, mesh);
+
+    assert_eq!(image.get_pixel(WIDTH / 2, HEIGHT / 2).0, [0, 255, 0, 255]);
+
+    // The typed entry points take the value itself
+    renderer
+        .update_mesh_uniform_typed(
+            mesh,
+            0,
+            &MeshUniform {
+                model: Mat4::from_translation(Vec3::new(0.5, 0.0, 0.0)),
+                color: Vec4::new(0.0, 0.0, 1.0, 1.0),
+            },
+        )
+        .unwrap();
+    renderer
+        .update_pipeline_global_typed(
+            pipeline,
+            0,
+            0,
+            &Camera {
+                view_projection: Mat4::from_translation(Vec3::new(0.5, 0.0, 0.0)),
+                position: Vec4::new(0.0, 0.0, -1.0, 1.0),
+            },
+        )
+        .unwrap();
+    let image = render_single(&mut renderer, mesh);
+
+    // Both translations move the quad to the right quarter
+    let background = *image.get_pixel(0, 0);
+    assert_eq!(*image.get_pixel(WIDTH / 2, HEIGHT / 2), background);
+    assert_eq!(
+        image.get_pixel(WIDTH * 7 / 8, HEIGHT / 2).0,
+        [0, 0, 255, 255]
+    );
+
+    // A value of another type does not fit the uniform
+    assert!(matches!(
+        renderer.update_mesh_uniform_typed(mesh, 0, &Mat4::IDENTITY),
+        Err(RendererError::UniformSize {
+            expected: 80,
+            actual: 64
+        })
+    ));
+}
+
+#[test]
+fn storage_buffers_in_uniform_groups() {
+    let mut renderer = headless_renderer(WIDTH, HEIGHT).unwrap();
+    let configuration = PipelineConfiguration::reflect(shader_source("storage.wgsl")).unwrap();
+    // Only the fragment stage reads the buffers, which downlevel adapters
+    // without vertex storage buffers require
+    assert!(configuration.bind_group_layouts[0]
+        .iter()
+        .all(|entry| entry.visibility == wgpu::ShaderStages::FRAGMENT));
+    let pipeline = renderer.register_pipeline(&configuration).unwrap();
+    let material = renderer
+        .register_material(&checkerboard_material())
+        .unwrap();
+
+    // A palette larger than the uniform buffer limit, the last entry is green
+    let limit = renderer2::Limits::downlevel_defaults().max_uniform_buffer_binding_size as usize;
+    let mut palette = vec![Vec4::new(1.0, 0.0, 0.0, 1.0); limit / 16 * 2];
+    let last = palette.len() - 1;
+    palette[last] = Vec4::new(0.0, 1.0, 0.0, 1.0);
+
+    let mut mesh_source = quad(pipeline, material, [1.0; 4]);
+    mesh_source.mesh_uniform_group.uniform_sources = vec![
+        UniformSource {
+            data: bytemuck::bytes_of(&[last as u32, 0, 0, 0]).to_vec(),
+            binding_type: BufferBindingType::Uniform,
+        },
+        UniformSource {
+            data: bytemuck::cast_slice(&palette).to_vec(),
+            binding_type: BufferBindingType::Storage { read_only: true },
+        },
+        UniformSource {
+            data: vec![0; 4],
+            binding_type: BufferBindingType::Storage { read_only: false },
+        },
+    ];
+
+    // The same data is too large for a uniform
+    let textured = renderer.register_pipeline(&textured_pipeline()).unwrap();
+    let mut too_large = quad(textured, material, [1.0; 4]);
+    too_large.mesh_uniform_group.uniform_sources[0].data = bytemuck::cast_slice(&palette).to_vec();
+    assert!(matches!(
+        renderer.register_mesh(&too_large),
+        Err(RendererError::UniformTooLarge { .. })
+    ));
+
+    // Binding types have to match the shader
+    let mut read_only = quad(pipeline, material, [1.0; 4]);
+    read_only.mesh_uniform_group.uniform_sources = vec![
+        UniformSource {
+            data: vec![0; 16],
+            binding_type: BufferBindingType::Uniform,
+        },
+        UniformSource {
+            data: vec![0; 16],
+            binding_type: BufferBindingType::Storage { read_only: true },
+        },
+        UniformSource {
+            data: vec![0; 4],
+            binding_type: BufferBindingType::Storage { read_only: true },
+        },
+    ];
+    assert!(matches!(
+        renderer.register_mesh(&read_only),
+        Err(RendererError::BindGroupMismatch { group: 0, .. })
+    ));
+
+    let mesh = renderer.register_mesh(&mesh_source).unwrap();
+    let image = render_single(&mut renderer, mesh);
+
+    assert_eq!(image.get_pixel(WIDTH / 2, HEIGHT / 2).0, [0, 255, 0, 255]);
+}